@@ -1,6 +1,7 @@
 use crate::config;
-use crate::context::{Context, Mode};
+use crate::context::{Context, Mode, Transport};
 use crate::output;
+use crate::pattern;
 use rosc::OscMessage;
 use std::collections::HashSet;
 
@@ -45,6 +46,39 @@ impl Pad {
     }
 }
 
+#[derive(Hash, PartialEq, Eq, Copy, Clone, Debug)]
+struct Button {
+    control: i32,
+}
+
+impl Button {
+    const LEFT: Button = Button { control: 90 };
+    const RIGHT: Button = Button { control: 102 };
+    const PLAY: Button = Button { control: 109 };
+    const STOP: Button = Button { control: 111 };
+    const SETUP: Button = Button { control: 86 };
+    const BANK: Button = Button { control: 26 };
+
+    fn turn_light_on_message(&self) -> OscMessage {
+        self.light_message(127)
+    }
+
+    fn turn_light_off_message(&self) -> OscMessage {
+        self.light_message(0)
+    }
+
+    fn light_message(&self, value: i32) -> OscMessage {
+        OscMessage {
+            addr: message_to_addr("control_change".to_string()),
+            args: vec![
+                rosc::OscType::Int(1),
+                rosc::OscType::Int(self.control),
+                rosc::OscType::Int(value),
+            ],
+        }
+    }
+}
+
 pub fn init() {
     handshake();
     turn_all_lights_off();
@@ -85,14 +119,30 @@ pub fn update(current_context: &Context, next_context: &Context) -> Vec<OscMessa
         osc_messages.push(pad_removed.turn_light_off_message());
     }
 
+    let current_context_active_buttons = active_buttons(current_context);
+    let next_context_active_buttons = active_buttons(next_context);
+
+    for button_added in next_context_active_buttons.difference(&current_context_active_buttons) {
+        osc_messages.push(button_added.turn_light_on_message());
+    }
+
+    for button_removed in current_context_active_buttons.difference(&next_context_active_buttons) {
+        osc_messages.push(button_removed.turn_light_off_message());
+    }
+
     osc_messages
 }
 
 fn current_pad(context: &Context) -> Option<Pad> {
     match context.mode {
         Mode::SequenceEdit => {
-            let current_step = context.selected_sequence().current_step(context.tick);
-            Some(Pad::new(current_step.0))
+            let current_step = context
+                .selected_sequence()
+                .current_step(context.pattern_tick(), &context.playback());
+            match current_step.page() == context.displayed_page() {
+                true => Some(Pad::new(current_step.pad())),
+                false => None,
+            }
         }
         _ => None,
     }
@@ -104,7 +154,14 @@ fn active_pads(context: &Context) -> HashSet<Pad> {
             .selected_sequence()
             .active_steps()
             .iter()
-            .map(|s| Pad::new(s.0))
+            .filter(|s| s.page() == context.displayed_page())
+            .map(|s| Pad::new(s.pad()))
+            .collect(),
+        Mode::PatternSelect => [Some(context.pattern), context.queued_pattern]
+            .iter()
+            .flatten()
+            .filter(|p| pattern::bank(**p) == context.bank)
+            .map(|p| Pad::from_sequence_number(pattern::number_in_bank(*p)))
             .collect(),
         Mode::Performance | Mode::SequenceMute | Mode::SequenceSelect => active_sequences(context)
             .iter()
@@ -113,11 +170,56 @@ fn active_pads(context: &Context) -> HashSet<Pad> {
     }
 }
 
+// The left and right buttons are lit when there are more pages of the
+// selected sequence or banks of patterns in that direction, play or stop
+// shows the transport, setup offers to restore an autosave left by the last
+// run and bank shows that a pattern is waiting to take over
+fn active_buttons(context: &Context) -> HashSet<Button> {
+    let mut active_buttons = HashSet::new();
+
+    if context.recovery_available {
+        active_buttons.insert(Button::SETUP);
+    }
+
+    if context.queued_pattern.is_some() {
+        active_buttons.insert(Button::BANK);
+    }
+
+    match context.transport {
+        Transport::Playing => active_buttons.insert(Button::PLAY),
+        Transport::Stopped | Transport::Paused => active_buttons.insert(Button::STOP),
+    };
+
+    let pages = match context.mode {
+        Mode::SequenceEdit => Some((
+            context.displayed_page(),
+            context.selected_sequence().number_of_pages(),
+        )),
+        Mode::PatternSelect => Some((context.bank as i32, pattern::BANKS as i32)),
+        _ => None,
+    };
+
+    if let Some((page, number_of_pages)) = pages {
+        if page > 0 {
+            active_buttons.insert(Button::LEFT);
+        }
+
+        if page < number_of_pages - 1 {
+            active_buttons.insert(Button::RIGHT);
+        }
+    }
+
+    active_buttons
+}
+
 fn active_sequences(context: &Context) -> HashSet<usize> {
     let mut active_sequences = HashSet::new();
 
     for (idx, sequence) in context.sequences.iter().enumerate() {
-        if !sequence.events_for_tick(context.tick).is_empty() {
+        if !sequence
+            .events_for_tick(context.pattern_tick(), &context.playback())
+            .is_empty()
+        {
             active_sequences.insert(idx);
         }
     }
@@ -129,6 +231,19 @@ fn turn_all_lights_off() {
     for n in 1..16 {
         output::send_osc_message_to_o2m(Pad::new(n).turn_light_off_message());
     }
+
+    for button in [
+        Button::LEFT,
+        Button::RIGHT,
+        Button::PLAY,
+        Button::STOP,
+        Button::SETUP,
+        Button::BANK,
+    ]
+    .iter()
+    {
+        output::send_osc_message_to_o2m(button.turn_light_off_message());
+    }
 }
 
 fn message_to_addr(message: String) -> String {
@@ -136,6 +251,9 @@ fn message_to_addr(message: String) -> String {
     format!("/{}/{}", controller_addr, message)
 }
 
+#[cfg(test)]
+use crate::input::Message;
+
 #[test]
 fn test_message_to_addr() {
     assert_eq!("/atom/note_on", message_to_addr("note_on".to_string()));
@@ -195,3 +313,96 @@ fn test_turn_light_off_message() {
     assert_eq!(rosc::OscType::Int(36), message.args[1]);
     assert_eq!(rosc::OscType::Int(0), message.args[2]);
 }
+
+#[test]
+fn test_current_pad_follows_sequence_division() {
+    let context = Context {
+        tick: 12,
+        ..Context::default()
+    }
+    .select_sequence(0);
+
+    assert_eq!(Some(Pad::new(3)), current_pad(&context));
+
+    let context =
+        context.process_messages(vec![Message::ShiftOn, Message::KnobDecrement { number: 1 }]);
+
+    // a sixteenth triplet step is 4 ticks long
+    assert_eq!(Some(Pad::new(4)), current_pad(&context));
+}
+
+#[test]
+fn test_active_pads_on_second_page() {
+    let context = Context::default()
+        .select_sequence(0)
+        .process_messages(vec![Message::KnobIncrement { number: 1 }])
+        .toggle_step_for_selected_sequence(2)
+        .toggle_step_for_selected_sequence(17)
+        .process_messages(vec![Message::Right]);
+
+    assert_eq!(1, active_pads(&context).len());
+    assert!(active_pads(&context).contains(&Pad::new(1)));
+    assert_eq!(None, current_pad(&context));
+}
+
+#[test]
+fn test_active_buttons() {
+    let context = Context::default().select_sequence(0);
+    assert_eq!(1, active_buttons(&context).len());
+
+    let context = context.process_messages(vec![Message::KnobIncrement { number: 1 }]);
+    assert_eq!(2, active_buttons(&context).len());
+    assert!(active_buttons(&context).contains(&Button::RIGHT));
+
+    let context = context.process_messages(vec![Message::Right]);
+    assert_eq!(2, active_buttons(&context).len());
+    assert!(active_buttons(&context).contains(&Button::LEFT));
+}
+
+#[test]
+fn test_update_page_indicator() {
+    let context = Context::default().select_sequence(0);
+    let next_context = context.process_messages(vec![Message::KnobIncrement { number: 1 }]);
+
+    let messages = update(&context, &next_context);
+
+    assert_eq!(1, messages.len());
+    assert_eq!("/atom/control_change", messages[0].addr);
+    assert_eq!(rosc::OscType::Int(102), messages[0].args[1]);
+    assert_eq!(rosc::OscType::Int(127), messages[0].args[2]);
+}
+
+#[test]
+fn test_active_buttons_show_transport() {
+    let context = Context::default();
+    assert!(active_buttons(&context).contains(&Button::PLAY));
+
+    let context = context.process_messages(vec![Message::Stop]);
+    assert!(active_buttons(&context).contains(&Button::STOP));
+    assert!(!active_buttons(&context).contains(&Button::PLAY));
+}
+
+#[test]
+fn test_active_pads_pattern_select() {
+    let context = Context::default().process_messages(vec![
+        Message::Stop,
+        Message::QueuePattern { bank: 0, number: 2 },
+        Message::Play,
+        Message::BankOn,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+    ]);
+
+    assert_eq!(2, context.pattern);
+    assert_eq!(Some(0), context.queued_pattern);
+    assert_eq!(2, active_pads(&context).len());
+    assert!(active_pads(&context).contains(&Pad::new(1)));
+    assert!(active_pads(&context).contains(&Pad::new(3)));
+    assert!(active_buttons(&context).contains(&Button::BANK));
+    assert!(active_buttons(&context).contains(&Button::RIGHT));
+
+    let context = context.process_messages(vec![Message::Right]);
+    assert!(active_pads(&context).is_empty());
+}
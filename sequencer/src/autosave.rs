@@ -0,0 +1,150 @@
+use crate::context::Context;
+use crate::project;
+use crate::project::Project;
+use crossbeam::crossbeam_channel::{unbounded, Sender};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Periodically sends a snapshot of the project to a thread that writes it to
+// the recovery file, so that the tick thread never waits on the disk
+pub struct Autosave {
+    sender: Sender<Project>,
+    interval: Duration,
+    last_snapshot: Instant,
+}
+
+impl Autosave {
+    pub fn start(path: String, interval: Duration) -> Autosave {
+        let (sender, receiver) = unbounded::<Project>();
+
+        thread::spawn(move || {
+            let mut writer = Writer::new(path);
+            for project in receiver {
+                writer.write(&project);
+            }
+        });
+
+        Autosave {
+            sender,
+            interval,
+            last_snapshot: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, context: &Context, now: Instant) {
+        if now.duration_since(self.last_snapshot) >= self.interval {
+            self.last_snapshot = now;
+            self.sender.send(Project::from_context(context)).unwrap();
+        }
+    }
+}
+
+// Only writes when the project has changed, to spare the Pi's SD card
+struct Writer {
+    path: String,
+    last_json: Option<String>,
+}
+
+impl Writer {
+    fn new(path: String) -> Writer {
+        Writer {
+            path,
+            last_json: None,
+        }
+    }
+
+    fn write(&mut self, project: &Project) -> bool {
+        let json = match project.to_json() {
+            Ok(json) => json,
+            Err(e) => {
+                println!("Could not autosave: {}", e);
+                return false;
+            }
+        };
+
+        if self.last_json.as_ref() == Some(&json) {
+            return false;
+        }
+
+        match project::write_atomically(&self.path, json.as_bytes()) {
+            Ok(()) => {
+                self.last_json = Some(json);
+                true
+            }
+            Err(e) => {
+                println!("Could not autosave to {}: {}", self.path, e);
+                false
+            }
+        }
+    }
+}
+
+pub fn recovery_path(project_path: &str) -> String {
+    format!("{}.recovery", project_path)
+}
+
+// Set aside the recovery file left by the last run, before autosave
+// overwrites it, if it has anything the project file doesn't. Returns the
+// path it was moved to.
+pub fn take_recovery(project_path: &str) -> Option<String> {
+    let recovery_path = recovery_path(project_path);
+    let recovered = fs::read_to_string(&recovery_path).ok()?;
+    let previous_path = format!("{}.previous", recovery_path);
+
+    let saved = Project::load(project_path)
+        .ok()
+        .and_then(|p| p.to_json().ok());
+    if saved.as_ref() == Some(&recovered) {
+        return None;
+    }
+
+    fs::rename(&recovery_path, &previous_path).ok()?;
+    Some(previous_path)
+}
+
+#[cfg(test)]
+fn temporary_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("brain-{}-{}", name, std::process::id()));
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_writer_only_writes_changes() {
+    let path = temporary_path("autosave");
+    let mut writer = Writer::new(path.clone());
+    let project = Project::default();
+
+    assert!(writer.write(&project));
+    assert!(!writer.write(&project));
+    assert!(writer.write(&Project {
+        bpm: 100.0,
+        ..project
+    }));
+    assert_eq!(100.0, Project::load(&path).unwrap().bpm);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_take_recovery() {
+    let project_path = temporary_path("recovery");
+    let project = Project::default();
+    project.save(&project_path).unwrap();
+
+    project.save(&recovery_path(&project_path)).unwrap();
+    assert_eq!(None, take_recovery(&project_path));
+
+    Project {
+        bpm: 100.0,
+        ..project
+    }
+    .save(&recovery_path(&project_path))
+    .unwrap();
+    let previous_path = take_recovery(&project_path).unwrap();
+    assert_eq!(100.0, Project::load(&previous_path).unwrap().bpm);
+    assert!(fs::metadata(recovery_path(&project_path)).is_err());
+
+    fs::remove_file(project_path).unwrap();
+    fs::remove_file(previous_path).unwrap();
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Condition {
+    #[default]
+    Always,
+    Probability(i32),
+    Ratio(i32, i32),
+    Fill,
+    NotFill,
+    First,
+    NotFirst,
+    Previous,
+    NotPrevious,
+}
+
+// The probabilities offered by Elektron sequencers
+const PROBABILITIES: [i32; 21] = [
+    1, 2, 4, 6, 9, 13, 19, 25, 33, 41, 50, 59, 67, 75, 81, 87, 91, 94, 96, 98, 99,
+];
+
+impl Condition {
+    pub fn all() -> Vec<Condition> {
+        let mut conditions = vec![Condition::Always];
+
+        for p in PROBABILITIES.iter() {
+            conditions.push(Condition::Probability(*p));
+        }
+
+        for b in 2..=8 {
+            for a in 1..=b {
+                conditions.push(Condition::Ratio(a, b));
+            }
+        }
+
+        conditions.push(Condition::Fill);
+        conditions.push(Condition::NotFill);
+        conditions.push(Condition::First);
+        conditions.push(Condition::NotFirst);
+        conditions.push(Condition::Previous);
+        conditions.push(Condition::NotPrevious);
+
+        conditions
+    }
+
+    pub fn next(&self) -> Condition {
+        let conditions = Condition::all();
+        match conditions.iter().position(|c| c == self) {
+            Some(i) if i + 1 < conditions.len() => conditions[i + 1],
+            _ => *self,
+        }
+    }
+
+    pub fn previous(&self) -> Condition {
+        let conditions = Condition::all();
+        match conditions.iter().position(|c| c == self) {
+            Some(i) if i > 0 => conditions[i - 1],
+            _ => *self,
+        }
+    }
+
+    pub fn is_previous(&self) -> bool {
+        matches!(self, Condition::Previous | Condition::NotPrevious)
+    }
+
+    pub fn is_met<F>(&self, loop_number: i32, fill: bool, random: i32, previous: F) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        match self {
+            Condition::Always => true,
+            Condition::Probability(p) => random < *p,
+            Condition::Ratio(a, b) => loop_number.rem_euclid(*b) == a - 1,
+            Condition::Fill => fill,
+            Condition::NotFill => !fill,
+            Condition::First => loop_number == 0,
+            Condition::NotFirst => loop_number != 0,
+            Condition::Previous => previous(),
+            Condition::NotPrevious => !previous(),
+        }
+    }
+}
+
+#[test]
+fn test_ratio() {
+    let condition = Condition::Ratio(3, 4);
+    let met: Vec<bool> = (0..8)
+        .map(|n| condition.is_met(n, false, 0, || false))
+        .collect();
+
+    assert_eq!(
+        vec![false, false, true, false, false, false, true, false],
+        met
+    );
+}
+
+#[test]
+fn test_probability() {
+    assert!(Condition::Probability(50).is_met(0, false, 49, || false));
+    assert!(!Condition::Probability(50).is_met(0, false, 50, || false));
+}
+
+#[test]
+fn test_fill_and_first() {
+    assert!(Condition::Fill.is_met(0, true, 0, || false));
+    assert!(!Condition::Fill.is_met(0, false, 0, || false));
+    assert!(Condition::NotFill.is_met(0, false, 0, || false));
+    assert!(Condition::First.is_met(0, false, 0, || false));
+    assert!(!Condition::First.is_met(1, false, 0, || false));
+    assert!(Condition::NotFirst.is_met(1, false, 0, || false));
+}
+
+#[test]
+fn test_previous() {
+    assert!(Condition::Previous.is_met(0, false, 0, || true));
+    assert!(!Condition::NotPrevious.is_met(0, false, 0, || true));
+}
+
+#[test]
+fn test_next_and_previous_condition() {
+    assert_eq!(Condition::Probability(1), Condition::Always.next());
+    assert_eq!(Condition::Always, Condition::Always.previous());
+    assert_eq!(Condition::Ratio(1, 2), Condition::Probability(99).next());
+    assert_eq!(Condition::NotPrevious, Condition::NotPrevious.next());
+}
@@ -1,4 +1,5 @@
 use clap::{App, Arg};
+use std::time::Duration;
 
 pub fn parse() -> clap::ArgMatches {
     App::new("brain")
@@ -7,6 +8,44 @@ pub fn parse() -> clap::ArgMatches {
                 .long("controller")
                 .default_value("atom"),
         )
+        .arg(
+            Arg::with_name("midi-output")
+                .long("midi-output")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("lookahead")
+                .long("lookahead")
+                .about("Milliseconds that sequenced events are delayed by, so that the sampler and MIDI can play them on time")
+                .default_value("50"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .about("A project file to load on startup and save to")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("autosave")
+                .long("autosave")
+                .about(
+                    "Seconds between autosaves of the project to a recovery file, or 0 to turn off",
+                )
+                .default_value("30"),
+        )
+        .arg(
+            Arg::with_name("clock")
+                .long("clock")
+                .about("Follow an external MIDI clock received from m2o, or send our own")
+                .possible_values(&["internal", "external"])
+                .default_value("internal"),
+        )
+        .arg(
+            Arg::with_name("clock-output")
+                .long("clock-output")
+                .about("The o2m port that clock and transport messages are sent to")
+                .default_value("*"),
+        )
         .get_matches()
 }
 
@@ -14,3 +53,37 @@ pub fn controller_addr() -> String {
     let config = parse();
     config.value_of("controller").unwrap().to_string()
 }
+
+pub fn midi_output() -> Option<String> {
+    let config = parse();
+    config.value_of("midi-output").map(|s| s.to_string())
+}
+
+pub fn lookahead() -> Duration {
+    let config = parse();
+    let milliseconds = config.value_of("lookahead").unwrap().parse().unwrap_or(50);
+    Duration::from_millis(milliseconds)
+}
+
+pub fn external_clock() -> bool {
+    let config = parse();
+    config.value_of("clock") == Some("external")
+}
+
+pub fn clock_output() -> String {
+    let config = parse();
+    config.value_of("clock-output").unwrap().to_string()
+}
+
+pub fn project() -> Option<String> {
+    let config = parse();
+    config.value_of("project").map(|s| s.to_string())
+}
+
+pub fn autosave() -> Option<Duration> {
+    let config = parse();
+    match config.value_of("autosave").unwrap().parse().unwrap_or(30) {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    }
+}
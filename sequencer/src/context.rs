@@ -1,16 +1,89 @@
-use crate::event::Event;
+use crate::division::Division;
+use crate::event::{Event, NoteOff, DEFAULT_LENGTH};
+use crate::history;
+use crate::history::{Edit, History};
 use crate::input::Message;
-use crate::sequence::Sequence;
+use crate::pattern;
+use crate::pattern::Pattern;
+use crate::project;
 use crate::sequence::Step;
+use crate::sequence::{Playback, Sequence};
+use crate::song::{Loop, Song};
+use crate::swing::{Resolution, Swing};
+use crate::tempo;
+use crate::tempo::TempoRamp;
+use crate::time_signature::{Position, TimeSignature};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
 
 #[derive(Debug, Clone)]
 pub struct Context {
     pub sequences: Vec<Sequence>,
     pub selected_sequence: usize,
+    pub pattern: usize,
+    pub pattern_bpm: Option<f32>,
+    pub queued_pattern: Option<usize>,
+    pub pattern_switch: pattern::Switch,
+    pub pattern_start: i32,
+    // The sequences of the other patterns, shared so that cloning the context
+    // each tick doesn't copy them
+    pub patterns: Arc<BTreeMap<usize, Pattern>>,
+    pub bank: usize,
+    pub song: Song,
+    pub song_mode: bool,
+    pub song_entry: usize,
+    pub queued_song_entry: Option<usize>,
+    pub history: History,
+    pub clipboard: Option<Arc<Clipboard>>,
     pub performance_events: Vec<Event>,
+    pub project_request: Option<project::Request>,
+    pub recovery_available: bool,
+    pub pending_note_offs: Vec<NoteOff>,
     pub bpm: f32,
+    pub tempo_nudge: f32,
+    pub tempo_ramp: Option<TempoRamp>,
+    pub taps: Vec<Instant>,
+    pub swing: Swing,
+    pub time_signature: TimeSignature,
+    pub count_in: bool,
+    pub fill: bool,
+    pub seed: u64,
     pub mode: Mode,
+    pub shift: bool,
+    pub held_step: Option<HeldStep>,
+    pub page: i32,
+    pub page_follow: bool,
+    pub transport: Transport,
     pub tick: i32,
+    // Set by an external start, continue or song position, as the next clock
+    // plays the tick that was moved to rather than the one after it
+    pub hold_tick: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct HeldStep {
+    pub step: Step,
+    remove_on_release: bool,
+}
+
+// What was last copied on the ATOM, ready to paste
+#[derive(Debug, Clone)]
+pub enum Clipboard {
+    Sequence(Sequence),
+    Steps {
+        sequence: Sequence,
+        start: Step,
+        end: Step,
+    },
+    Pattern(Pattern),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Transport {
+    Stopped,
+    Playing,
+    Paused,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -18,26 +91,58 @@ pub enum Mode {
     SequenceEdit,
     SequenceMute,
     SequenceSelect,
+    PatternSelect,
     Performance,
 }
 
-impl Context {
-    pub fn default() -> Context {
-        let mut sequences = vec![];
-        for n in 36..=51 {
-            sequences.push(Sequence::with_default_note_number(n));
-        }
+// The count-in clicks on a claves, like a drum machine's metronome
+const COUNT_IN_NOTE_NUMBER: i32 = 75;
+const COUNT_IN_CHANNEL: i32 = 10;
 
+impl Default for Context {
+    fn default() -> Context {
         Context {
-            sequences,
+            sequences: Pattern::default().sequences,
             selected_sequence: 0,
+            pattern: 0,
+            pattern_bpm: None,
+            queued_pattern: None,
+            pattern_switch: pattern::Switch::default(),
+            pattern_start: 0,
+            patterns: Arc::new(BTreeMap::new()),
+            bank: 0,
+            song: Song::default(),
+            song_mode: false,
+            song_entry: 0,
+            queued_song_entry: None,
+            history: History::default(),
+            clipboard: None,
             performance_events: vec![],
+            project_request: None,
+            recovery_available: false,
+            pending_note_offs: vec![],
             bpm: 120.0,
+            tempo_nudge: 0.0,
+            tempo_ramp: None,
+            taps: vec![],
+            swing: Swing::default(),
+            time_signature: TimeSignature::default(),
+            count_in: false,
+            fill: false,
+            seed: 0,
             mode: Mode::Performance,
+            shift: false,
+            held_step: None,
+            page: 0,
+            page_follow: true,
+            transport: Transport::Playing,
             tick: 0,
+            hold_tick: false,
         }
     }
+}
 
+impl Context {
     pub fn process_messages(&self, messages: Vec<Message>) -> Context {
         match messages.len() {
             0 => self.clone(),
@@ -55,10 +160,256 @@ impl Context {
         }
     }
 
+    // Moves on a tick if the clock ticked, then handles the messages that
+    // arrived
+    pub fn next(&self, clocked: bool, messages: Vec<Message>) -> Context {
+        match (clocked, self.hold_tick) {
+            (true, true) => Context {
+                hold_tick: false,
+                ..self.clone()
+            },
+            (true, false) => self.advance_tick(),
+            (false, _) => self.clone(),
+        }
+        .process_messages(messages)
+    }
+
     pub fn advance_tick(&self) -> Context {
-        let mut new_context = self.clone();
-        new_context.tick += 1;
-        new_context
+        match self.transport {
+            Transport::Playing => Context {
+                tick: self.tick + 1,
+                ..self.clone()
+            }
+            .follow_tempo_ramp()
+            .follow_pattern_queue()
+            .follow_song(),
+            Transport::Stopped | Transport::Paused => self.follow_tempo_ramp(),
+        }
+    }
+
+    // The tick within the pattern that is playing, which starts from the
+    // beginning when it takes over from another pattern
+    pub fn pattern_tick(&self) -> i32 {
+        self.tick - self.pattern_start
+    }
+
+    // Every pattern, including the one that is playing
+    pub fn patterns(&self) -> BTreeMap<usize, Pattern> {
+        let mut patterns = (*self.patterns).clone();
+        patterns.insert(self.pattern, self.pattern_at(self.pattern));
+        patterns
+    }
+
+    fn pattern_at(&self, pattern: usize) -> Pattern {
+        match pattern == self.pattern {
+            true => Pattern {
+                bpm: self.pattern_bpm,
+                sequences: self.sequences.clone(),
+            },
+            false => self.patterns.get(&pattern).cloned().unwrap_or_default(),
+        }
+    }
+
+    // Pasting over a pattern that isn't playing is recorded as an edit here,
+    // as only edits to the playing pattern are noticed by record_edit
+    fn paste_pattern(&self, pattern: usize, source: &Pattern) -> Context {
+        match pattern == self.pattern {
+            true => Context {
+                sequences: source.sequences.clone(),
+                pattern_bpm: source.bpm,
+                held_step: None,
+                ..self.flush_all_note_offs()
+            },
+            false => {
+                let mut patterns = self.patterns.clone();
+                Arc::make_mut(&mut patterns).insert(pattern, source.clone());
+
+                Context {
+                    patterns,
+                    history: self.history.record(self.edit(pattern)),
+                    ..self.clone()
+                }
+            }
+        }
+    }
+
+    fn copy_pattern(&self, from: usize, to: usize) -> Context {
+        self.paste_pattern(to, &self.pattern_at(from))
+    }
+
+    // A pattern chosen while playing waits for the end of the bar or pattern,
+    // otherwise it takes over straight away
+    fn queue_pattern(&self, pattern: usize) -> Context {
+        match (pattern == self.pattern, self.transport) {
+            (true, _) => Context {
+                queued_pattern: None,
+                ..self.clone()
+            },
+            (false, Transport::Playing) => Context {
+                queued_pattern: Some(pattern),
+                ..self.clone()
+            },
+            (false, Transport::Stopped) | (false, Transport::Paused) => {
+                self.switch_pattern(pattern)
+            }
+        }
+    }
+
+    fn at_switch_point(&self) -> bool {
+        let length = match self.pattern_switch {
+            pattern::Switch::Bar => self.time_signature.ticks_per_bar(),
+            pattern::Switch::Pattern => Pattern::length_in_ticks(&self.sequences),
+        };
+
+        self.tick >= 0 && self.pattern_tick() % length.max(1) == 0
+    }
+
+    fn follow_pattern_queue(&self) -> Context {
+        match self.queued_pattern {
+            Some(pattern) if self.at_switch_point() => self.switch_pattern(pattern),
+            _ => self.clone(),
+        }
+    }
+
+    // In song mode each entry's pattern plays through its number of repeats
+    // before the next entry takes over, unless a jump to another entry is
+    // waiting
+    fn follow_song(&self) -> Context {
+        let entry = match self.song.entry(self.song_entry) {
+            Some(entry) if self.song_mode => entry,
+            _ => return self.clone(),
+        };
+
+        if let Some(next) = self.queued_song_entry {
+            if self.at_switch_point() {
+                return self.start_song_entry(next);
+            }
+        }
+
+        let length = Pattern::length_in_ticks(&self.sequences).max(1);
+        match self.pattern_tick() >= length * entry.repeats {
+            true => match self.song.next_entry(self.song_entry) {
+                Some(next) => self.start_song_entry(next),
+                None => self.stop(),
+            },
+            false => self.clone(),
+        }
+    }
+
+    fn start_song_entry(&self, entry: usize) -> Context {
+        match self.song.entry(entry) {
+            Some(e) => Context {
+                song_entry: entry,
+                queued_song_entry: None,
+                ..self.switch_pattern(e.pattern)
+            },
+            None => self.clone(),
+        }
+    }
+
+    // While playing a jump waits for the end of the bar or pattern, like a
+    // queued pattern
+    fn jump_to_song_entry(&self, entry: usize) -> Context {
+        match (entry < self.song.entries.len(), self.transport) {
+            (false, _) => self.clone(),
+            (true, Transport::Playing) if self.song_mode => Context {
+                queued_song_entry: Some(entry),
+                ..self.clone()
+            },
+            (true, _) if self.song_mode => self.start_song_entry(entry),
+            (true, _) => Context {
+                song_entry: entry,
+                ..self.clone()
+            },
+        }
+    }
+
+    fn set_song_mode(&self, on: bool) -> Context {
+        let context = Context {
+            song_mode: on,
+            queued_song_entry: None,
+            ..self.clone()
+        };
+
+        match on {
+            true => context.jump_to_song_entry(self.song_entry),
+            false => context,
+        }
+    }
+
+    fn change_song<F>(&self, f: F) -> Context
+    where
+        F: Fn(&Song) -> Song,
+    {
+        let song = f(&self.song);
+
+        Context {
+            song_entry: self.song_entry.min(song.entries.len().saturating_sub(1)),
+            queued_song_entry: self.queued_song_entry.filter(|e| *e < song.entries.len()),
+            song,
+            ..self.clone()
+        }
+    }
+
+    fn switch_pattern(&self, pattern: usize) -> Context {
+        let mut patterns = self.patterns.clone();
+        Arc::make_mut(&mut patterns).insert(self.pattern, self.pattern_at(self.pattern));
+        let next = patterns.get(&pattern).cloned().unwrap_or_default();
+
+        Context {
+            sequences: next.sequences,
+            pattern,
+            pattern_bpm: next.bpm,
+            queued_pattern: None,
+            pattern_start: self.tick,
+            patterns,
+            bpm: next.bpm.unwrap_or(self.bpm),
+            tempo_ramp: match next.bpm {
+                Some(_) => None,
+                None => self.tempo_ramp,
+            },
+            held_step: None,
+            page: 0,
+            ..self.flush_all_note_offs()
+        }
+    }
+
+    // Gives the playing pattern a tempo of its own, or clears it so the
+    // pattern plays at whatever tempo is set
+    fn set_pattern_tempo(&self, bpm: Option<f32>) -> Context {
+        match bpm {
+            Some(bpm) => Context {
+                bpm: tempo::clamp_bpm(bpm),
+                pattern_bpm: Some(tempo::clamp_bpm(bpm)),
+                tempo_ramp: None,
+                ..self.clone()
+            },
+            None => Context {
+                pattern_bpm: None,
+                ..self.clone()
+            },
+        }
+    }
+
+    fn change_bank(&self, amount: i32) -> Context {
+        Context {
+            bank: (self.bank as i32 + amount).clamp(0, pattern::BANKS as i32 - 1) as usize,
+            ..self.clone()
+        }
+    }
+
+    fn follow_tempo_ramp(&self) -> Context {
+        match self.tempo_ramp {
+            Some(ramp) => Context {
+                bpm: ramp.bpm_at(self.tick),
+                tempo_ramp: match ramp.is_finished(self.tick) {
+                    true => None,
+                    false => Some(ramp),
+                },
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
     }
 
     pub fn clear_performance_events(&self) -> Context {
@@ -67,8 +418,143 @@ impl Context {
         new_context
     }
 
+    pub fn clear_project_request(&self) -> Context {
+        Context {
+            project_request: None,
+            ..self.clone()
+        }
+    }
+
+    fn request_project(&self, request: project::Request) -> Context {
+        Context {
+            project_request: Some(request),
+            ..self.clone()
+        }
+    }
+
+    // Slots without a sequence are left empty
+    fn replace_sequences(&self, sequences: &[Sequence]) -> Context {
+        let sequences = Pattern::default()
+            .sequences
+            .iter()
+            .enumerate()
+            .map(|(n, empty)| sequences.get(n).unwrap_or(empty).clone())
+            .collect();
+
+        Context {
+            sequences,
+            held_step: None,
+            ..self.clone()
+        }
+        .flush_all_note_offs()
+    }
+
+    // Shift moves performance mode into mute mode while it is held
+    fn shift_held(&self) -> bool {
+        self.shift || matches!(self.mode, Mode::SequenceMute)
+    }
+
+    pub fn note_offs(&self) -> Vec<NoteOff> {
+        self.note_offs_for(&self.events())
+    }
+
+    // The note-offs that are due without the tick moving on, for when live
+    // hits are played between ticks
+    pub fn performance_note_offs(&self) -> Vec<NoteOff> {
+        self.note_offs_for(&self.performance_events)
+    }
+
+    // The pending note-offs for notes that a live hit plays again
+    pub fn retriggered_note_offs(&self) -> Vec<NoteOff> {
+        self.pending_note_offs
+            .iter()
+            .filter(|n| {
+                self.performance_events
+                    .iter()
+                    .any(|e| e.note_number == n.note_number && e.channel == n.channel)
+            })
+            .copied()
+            .collect()
+    }
+
+    fn note_offs_for(&self, events: &[Event]) -> Vec<NoteOff> {
+        self.pending_note_offs
+            .iter()
+            .filter(|n| {
+                n.tick <= self.tick
+                    || events
+                        .iter()
+                        .any(|e| e.note_number == n.note_number && e.channel == n.channel)
+            })
+            .copied()
+            .collect()
+    }
+
+    pub fn schedule_note_offs(&self) -> Context {
+        self.schedule_note_offs_for(&self.events())
+    }
+
+    pub fn schedule_performance_note_offs(&self) -> Context {
+        self.schedule_note_offs_for(&self.performance_events)
+    }
+
+    fn schedule_note_offs_for(&self, events: &[Event]) -> Context {
+        let note_offs = self.note_offs_for(events);
+        let mut pending_note_offs: Vec<NoteOff> = self
+            .pending_note_offs
+            .iter()
+            .filter(|n| !note_offs.contains(n))
+            .copied()
+            .collect();
+
+        for event in events {
+            pending_note_offs.push(event.note_off(self.tick));
+        }
+
+        Context {
+            pending_note_offs,
+            ..self.clone()
+        }
+    }
+
+    fn flush_note_offs(&self, sequence_number: usize) -> Context {
+        let channel = self.sequences[sequence_number].midi_channel();
+        let pending_note_offs = self
+            .pending_note_offs
+            .iter()
+            .map(|n| match n.channel == channel {
+                true => NoteOff {
+                    tick: self.tick,
+                    ..*n
+                },
+                false => *n,
+            })
+            .collect();
+
+        Context {
+            pending_note_offs,
+            ..self.clone()
+        }
+    }
+
+    fn flush_all_note_offs(&self) -> Context {
+        let pending_note_offs = self
+            .pending_note_offs
+            .iter()
+            .map(|n| NoteOff {
+                tick: self.tick,
+                ..*n
+            })
+            .collect();
+
+        Context {
+            pending_note_offs,
+            ..self.clone()
+        }
+    }
+
     pub fn events(&self) -> Vec<Event> {
-        let mut events = self.events_for_tick(self.tick);
+        let mut events = self.sequenced_events();
 
         for event in &self.performance_events {
             events.push(*event);
@@ -77,17 +563,153 @@ impl Context {
         events
     }
 
+    // The events from the sequences, which unlike performance events are
+    // known ahead of time
+    pub fn sequenced_events(&self) -> Vec<Event> {
+        match self.transport {
+            Transport::Playing if self.tick < 0 => self.count_in_events(),
+            Transport::Playing => self.events_for_tick(self.pattern_tick()),
+            Transport::Stopped | Transport::Paused => vec![],
+        }
+    }
+
+    // While counting in the tick is negative, and a click sounds on each beat
+    fn count_in_events(&self) -> Vec<Event> {
+        let position = self.position();
+
+        match position.tick {
+            0 => vec![Event {
+                note_number: COUNT_IN_NOTE_NUMBER,
+                velocity: match position.beat {
+                    1 => 127,
+                    _ => 90,
+                },
+                length: DEFAULT_LENGTH,
+                channel: COUNT_IN_CHANNEL,
+            }],
+            _ => vec![],
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.time_signature.position(self.tick)
+    }
+
+    // Sequences that were a bar long stay a bar long in the new time
+    // signature
+    fn set_time_signature(&self, time_signature: TimeSignature) -> Context {
+        let sequences = self
+            .sequences
+            .iter()
+            .map(|s| {
+                let one_bar = self.time_signature.steps_per_bar(s.division());
+                match s.number_of_steps() == one_bar {
+                    true => s.set_length(time_signature.steps_per_bar(s.division())),
+                    false => s.clone(),
+                }
+            })
+            .collect();
+
+        Context {
+            sequences,
+            time_signature,
+            ..self.clone()
+        }
+    }
+
     fn events_for_tick(&self, tick_number: i32) -> Vec<Event> {
         self.sequences
             .iter()
-            .flat_map(|s| s.events_for_tick(tick_number))
+            .flat_map(|s| s.events_for_tick(tick_number, &self.playback()))
             .collect()
     }
 
+    // The tempo to play at, including any temporary nudge
+    pub fn tempo(&self) -> f32 {
+        self.bpm * (1.0 + self.tempo_nudge)
+    }
+
+    // Changing the tempo by hand cancels any ramp in progress
+    fn change_bpm(&self, amount: f32) -> Context {
+        Context {
+            bpm: tempo::clamp_bpm(self.bpm + amount),
+            tempo_ramp: None,
+            ..self.clone()
+        }
+    }
+
+    fn tap(&self, time: Instant) -> Context {
+        let taps = tempo::add_tap(&self.taps, time);
+
+        match tempo::tap_tempo(&taps) {
+            Some(bpm) => Context {
+                bpm,
+                taps,
+                tempo_ramp: None,
+                ..self.clone()
+            },
+            None => Context {
+                taps,
+                ..self.clone()
+            },
+        }
+    }
+
+    // Ramps from the current tempo to the given tempo over a number of bars
+    fn ramp_tempo(&self, bpm: f32, bars: i32) -> Context {
+        Context {
+            tempo_ramp: Some(TempoRamp {
+                from: self.bpm,
+                to: tempo::clamp_bpm(bpm),
+                start_tick: self.tick,
+                length: bars.max(0) * self.time_signature.ticks_per_bar(),
+            }),
+            ..self.clone()
+        }
+        .follow_tempo_ramp()
+    }
+
+    fn nudge_tempo(&self, tempo_nudge: f32) -> Context {
+        Context {
+            tempo_nudge,
+            ..self.clone()
+        }
+    }
+
+    pub fn playback(&self) -> Playback {
+        Playback {
+            swing: self.swing,
+            fill: self.fill,
+            seed: self.seed,
+        }
+    }
+
     pub fn select_sequence(&self, sequence_number: usize) -> Context {
         Context {
             selected_sequence: sequence_number,
             mode: Mode::SequenceEdit,
+            page: 0,
+            ..self.clone()
+        }
+    }
+
+    pub fn displayed_page(&self) -> i32 {
+        let sequence = self.selected_sequence();
+
+        match self.page_follow {
+            true => sequence
+                .current_step(self.pattern_tick(), &self.playback())
+                .page(),
+            false => self.page.min(sequence.number_of_pages() - 1),
+        }
+    }
+
+    fn change_page(&self, amount: i32) -> Context {
+        let last_page = self.selected_sequence().number_of_pages() - 1;
+
+        Context {
+            page: (self.displayed_page() + amount).clamp(0, last_page),
+            page_follow: false,
             ..self.clone()
         }
     }
@@ -116,6 +738,7 @@ impl Context {
             sequences,
             ..self.clone()
         }
+        .flush_note_offs(sequence_number)
     }
 
     pub fn toggle_step_for_selected_sequence(&self, step_number: i32) -> Context {
@@ -129,38 +752,541 @@ impl Context {
         }
     }
 
+    fn press_step(&self, step: Step, velocity: i32) -> Context {
+        let sequence = self.selected_sequence();
+
+        match sequence.active_steps().contains(&step) {
+            true => Context {
+                held_step: Some(HeldStep {
+                    step,
+                    remove_on_release: true,
+                }),
+                ..self.clone()
+            },
+            false => Context {
+                held_step: Some(HeldStep {
+                    step,
+                    remove_on_release: false,
+                }),
+                ..self.change_selected_sequence(|s| s.toggle_step_with_velocity(step, velocity))
+            },
+        }
+    }
+
+    fn release_step(&self, pad: i32) -> Context {
+        match self.held_step {
+            Some(held_step) if held_step.step.pad() == pad => {
+                let step = held_step.step;
+                let context = Context {
+                    held_step: None,
+                    ..self.clone()
+                };
+
+                match held_step.remove_on_release {
+                    true => context.change_selected_sequence(|s| s.toggle_step(step)),
+                    false => context,
+                }
+            }
+            _ => self.clone(),
+        }
+    }
+
+    fn change_held_step<F>(&self, f: F) -> Context
+    where
+        F: Fn(&Sequence, Step) -> Sequence,
+    {
+        match self.held_step {
+            Some(held_step) => Context {
+                held_step: Some(HeldStep {
+                    remove_on_release: false,
+                    ..held_step
+                }),
+                ..self.change_selected_sequence(|s| f(s, held_step.step))
+            },
+            None => self.clone(),
+        }
+    }
+
     fn change_selected_sequence<F>(&self, f: F) -> Context
+    where
+        F: Fn(&Sequence) -> Sequence,
+    {
+        self.change_sequence(self.selected_sequence, f)
+    }
+
+    fn change_sequence<F>(&self, sequence_number: usize, f: F) -> Context
     where
         F: Fn(&Sequence) -> Sequence,
     {
         let mut sequences = self.sequences.clone();
-        let new_sequence = f(&self.sequences[self.selected_sequence]);
-        sequences[self.selected_sequence] = new_sequence;
 
-        Context {
-            sequences,
-            ..self.clone()
+        match sequences.get_mut(sequence_number) {
+            Some(sequence) => {
+                *sequence = f(sequence);
+
+                Context {
+                    sequences,
+                    ..self.clone()
+                }
+            }
+            None => self.clone(),
         }
     }
 
-    pub fn set_mode(&self, mode: Mode) -> Context {
-        Context {
-            mode,
-            ..self.clone()
+    fn copy_sequence(&self, from: usize, to: usize) -> Context {
+        match (self.sequences.get(from), to < self.sequences.len()) {
+            (Some(source), true) => self
+                .change_sequence(to, |s| s.paste(source))
+                .flush_note_offs(to),
+            _ => self.clone(),
         }
     }
 
-    fn process_message(&self, message: &Message) -> Context {
-        match self.mode {
-            Mode::SequenceEdit => match message {
-                Message::NoteOn { note_number: n } => {
-                    self.toggle_step_for_selected_sequence(note_number_to_sixteenth(*n))
+    fn clear_sequence(&self, sequence_number: usize) -> Context {
+        match sequence_number < self.sequences.len() {
+            true => self
+                .change_sequence(sequence_number, Sequence::clear)
+                .flush_note_offs(sequence_number),
+            false => self.clone(),
+        }
+    }
+
+    fn clear_all(&self) -> Context {
+        Context {
+            sequences: self.sequences.iter().map(Sequence::clear).collect(),
+            held_step: None,
+            ..self.flush_all_note_offs()
+        }
+    }
+
+    fn copy(&self, clipboard: Clipboard) -> Context {
+        Context {
+            clipboard: Some(Arc::new(clipboard)),
+            ..self.clone()
+        }
+    }
+
+    // Holding a step and pressing another with shift copies the steps
+    // between them
+    fn copy_held_steps(&self, pad: i32) -> Context {
+        match self.held_step {
+            Some(held_step) => {
+                let step = Step::on_page(self.displayed_page(), pad);
+
+                Context {
+                    held_step: Some(HeldStep {
+                        remove_on_release: false,
+                        ..held_step
+                    }),
+                    ..self.copy(Clipboard::Steps {
+                        sequence: self.selected_sequence().clone(),
+                        start: Step(held_step.step.0.min(step.0)),
+                        end: Step(held_step.step.0.max(step.0)),
+                    })
+                }
+            }
+            None => self.clone(),
+        }
+    }
+
+    // Steps are pasted from the held step, or the start of the page
+    fn paste_into_selected_sequence(&self) -> Context {
+        let clipboard = match &self.clipboard {
+            Some(clipboard) => clipboard.clone(),
+            None => return self.clone(),
+        };
+
+        match &*clipboard {
+            Clipboard::Sequence(source) => self
+                .change_selected_sequence(|s| s.paste(source))
+                .flush_note_offs(self.selected_sequence),
+            Clipboard::Steps {
+                sequence,
+                start,
+                end,
+            } => {
+                let destination = match self.held_step {
+                    Some(held_step) => held_step.step,
+                    None => Step::on_page(self.displayed_page(), 1),
+                };
+
+                Context {
+                    held_step: self.held_step.map(|h| HeldStep {
+                        remove_on_release: false,
+                        ..h
+                    }),
+                    ..self.change_selected_sequence(|s| {
+                        s.copy_steps(sequence, *start, *end, destination)
+                    })
+                }
+            }
+            Clipboard::Pattern(_) => self.clone(),
+        }
+    }
+
+    fn paste_pattern_from_clipboard(&self, pattern: usize) -> Context {
+        match self.clipboard.as_deref() {
+            Some(Clipboard::Pattern(source)) => self.paste_pattern(pattern, source),
+            _ => self.clone(),
+        }
+    }
+
+    // Play starts from the beginning when stopped, and toggles between
+    // playing and paused otherwise
+    fn play(&self) -> Context {
+        match self.transport {
+            Transport::Stopped => match self.count_in {
+                true => Context {
+                    tick: -self.time_signature.ticks_per_bar(),
+                    ..self.start()
+                },
+                false => self.start(),
+            },
+            Transport::Playing => self.pause(),
+            Transport::Paused => self.continue_playing(),
+        }
+    }
+
+    // Song mode starts from the chosen entry, which is the first unless
+    // another was jumped to while stopped
+    fn start(&self) -> Context {
+        let context = Context {
+            transport: Transport::Playing,
+            tick: 0,
+            pattern_start: 0,
+            ..self.clone()
+        };
+
+        match self.song_mode {
+            true => context.start_song_entry(self.song_entry),
+            false => context,
+        }
+    }
+
+    fn pause(&self) -> Context {
+        match self.transport {
+            Transport::Playing => Context {
+                transport: Transport::Paused,
+                ..self.flush_all_note_offs()
+            },
+            Transport::Stopped | Transport::Paused => self.clone(),
+        }
+    }
+
+    fn continue_playing(&self) -> Context {
+        Context {
+            transport: Transport::Playing,
+            ..self.clone()
+        }
+    }
+
+    fn stop(&self) -> Context {
+        Context {
+            transport: Transport::Stopped,
+            tick: 0,
+            pattern_start: 0,
+            song_entry: 0,
+            queued_song_entry: None,
+            tempo_ramp: None,
+            ..self.clone()
+        }
+        .flush_all_note_offs()
+    }
+
+    pub fn set_mode(&self, mode: Mode) -> Context {
+        Context {
+            mode,
+            shift: false,
+            held_step: None,
+            ..self.clone()
+        }
+    }
+
+    // Loading a project, or undoing and redoing, replaces the sequences
+    // without being an edit itself
+    fn process_message(&self, message: &Message) -> Context {
+        match message {
+            Message::Undo => self.undo(),
+            Message::Redo => self.redo(),
+            Message::Left if self.shift_held() => self.undo(),
+            Message::Right if self.shift_held() => self.redo(),
+            Message::ProjectLoaded { .. } => self.process_global_message(message),
+            _ => self.process_global_message(message).record_edit(self),
+        }
+    }
+
+    // Switching patterns isn't an edit, even though the sequences change
+    fn record_edit(&self, previous: &Context) -> Context {
+        match self.pattern == previous.pattern
+            && history::is_edit(&previous.sequences, &self.sequences)
+        {
+            true => Context {
+                history: self.history.record(previous.edit(previous.pattern)),
+                ..self.clone()
+            },
+            false => self.clone(),
+        }
+    }
+
+    fn edit(&self, pattern: usize) -> Edit {
+        Edit {
+            pattern,
+            sequences: self.pattern_at(pattern).sequences,
+        }
+    }
+
+    fn undo(&self) -> Context {
+        match self.history.next_undo() {
+            Some(edit) => Context {
+                history: self.history.undo(self.edit(edit.pattern)),
+                ..self.restore(&edit)
+            },
+            None => self.clone(),
+        }
+    }
+
+    fn redo(&self) -> Context {
+        match self.history.next_redo() {
+            Some(edit) => Context {
+                history: self.history.redo(self.edit(edit.pattern)),
+                ..self.restore(&edit)
+            },
+            None => self.clone(),
+        }
+    }
+
+    // An edit to a pattern that isn't playing is undone in place, without
+    // switching to it
+    fn restore(&self, edit: &Edit) -> Context {
+        match edit.pattern == self.pattern {
+            true => Context {
+                sequences: edit.sequences_keeping_mutes(&self.sequences),
+                held_step: None,
+                ..self.clone()
+            }
+            .flush_all_note_offs(),
+            false => {
+                let mut patterns = self.patterns.clone();
+                let pattern = Arc::make_mut(&mut patterns)
+                    .entry(edit.pattern)
+                    .or_default();
+                pattern.sequences = edit.sequences_keeping_mutes(&pattern.sequences);
+
+                Context {
+                    patterns,
+                    ..self.clone()
+                }
+            }
+        }
+    }
+
+    fn process_global_message(&self, message: &Message) -> Context {
+        match message {
+            Message::FillOn => Context {
+                fill: true,
+                ..self.clone()
+            },
+            Message::FillOff => Context {
+                fill: false,
+                ..self.clone()
+            },
+            Message::Save { path } => {
+                self.request_project(project::Request::Save { path: path.clone() })
+            }
+            Message::Load { path } => {
+                self.request_project(project::Request::Load { path: path.clone() })
+            }
+            Message::SetupOn if self.shift_held() => {
+                self.request_project(project::Request::Save { path: None })
+            }
+            Message::EditorOn if self.shift_held() => {
+                self.request_project(project::Request::Load { path: None })
+            }
+            Message::SetupOn if self.recovery_available => {
+                self.request_project(project::Request::Restore)
+            }
+            Message::Restore => self.request_project(project::Request::Restore),
+            Message::ImportMidi {
+                path,
+                ticks_per_step,
+                by_track,
+            } => self.request_project(project::Request::ImportMidi {
+                path: path.clone(),
+                division: ticks_per_step.map(Division).unwrap_or_default(),
+                by_track: *by_track,
+            }),
+            Message::MidiImported { sequences } => self.replace_sequences(sequences),
+            Message::ExportMidi { path } => {
+                self.request_project(project::Request::ExportMidi { path: path.clone() })
+            }
+            Message::ProjectLoaded { project } => Context {
+                recovery_available: false,
+                ..project.apply(self)
+            },
+            Message::Tap { time } => self.tap(*time),
+            // A nudge ends on release in any mode, as shift or a mode change
+            // can come while left or right is held
+            Message::LeftOff | Message::RightOff => self.nudge_tempo(0.0),
+            Message::TempoRamp { bpm, bars } => self.ramp_tempo(*bpm, *bars),
+            Message::TimeSignature { beats, unit } => match TimeSignature::new(*beats, *unit) {
+                Some(time_signature) => self.set_time_signature(time_signature),
+                None => self.clone(),
+            },
+            Message::CountIn { on } => Context {
+                count_in: *on,
+                ..self.clone()
+            },
+            // Holding shift and fill, set loop clears the sequence being
+            // edited, or every sequence in the pattern from the other modes
+            Message::SetLoop if self.shift_held() && self.fill => match self.mode {
+                Mode::SequenceEdit => self.clear_sequence(self.selected_sequence),
+                _ => self.clear_all(),
+            },
+            Message::Play => self.play(),
+            Message::Stop => self.stop(),
+            Message::Continue => self.continue_playing(),
+            Message::ExternalContinue => Context {
+                hold_tick: true,
+                ..self.continue_playing()
+            },
+            Message::Start => Context {
+                hold_tick: true,
+                ..self.start()
+            },
+            Message::Pause => self.pause(),
+            // MIDI song position pointers count sixteenth notes
+            Message::SongPosition { position } => Context {
+                tick: position * 6,
+                pattern_start: 0,
+                hold_tick: true,
+                ..self.clone()
+            },
+            Message::QueuePattern { bank, number } => match pattern::index(*bank, *number) {
+                Some(pattern) => self.queue_pattern(pattern),
+                None => self.clone(),
+            },
+            Message::PatternSwitch { at } => Context {
+                pattern_switch: *at,
+                ..self.clone()
+            },
+            Message::PatternTempo { bpm } => self.set_pattern_tempo(*bpm),
+            Message::SongAppend {
+                bank,
+                number,
+                repeats,
+            } => match pattern::index(*bank, *number) {
+                Some(pattern) => self.change_song(|s| s.append(pattern, *repeats)),
+                None => self.clone(),
+            },
+            Message::SongRemove { entry } => self.change_song(|s| s.remove(*entry)),
+            Message::SongClear => self.change_song(|_| Song::default()),
+            Message::SongLoop { entries } => {
+                self.change_song(|s| s.set_loop(entries.map(|(start, end)| Loop { start, end })))
+            }
+            Message::SongMode { on } => self.set_song_mode(*on),
+            Message::SongJump { entry } => self.jump_to_song_entry(*entry),
+            Message::CopySequence { from, to } => self.copy_sequence(*from, *to),
+            Message::CopySteps {
+                sequence,
+                start,
+                end,
+                destination,
+            } => self.change_sequence(*sequence, |s| s.copy_steps(s, *start, *end, *destination)),
+            Message::DoubleSequence { sequence } => {
+                self.change_sequence(*sequence, Sequence::double)
+            }
+            Message::ClearSequence { sequence } => self.clear_sequence(*sequence),
+            Message::ClearAll => self.clear_all(),
+            Message::CopyPattern { from, to } => {
+                match (pattern::index(from.0, from.1), pattern::index(to.0, to.1)) {
+                    (Some(from), Some(to)) => self.copy_pattern(from, to),
+                    _ => self.clone(),
+                }
+            }
+            _ => self.process_message_for_mode(message),
+        }
+    }
+
+    fn process_message_for_mode(&self, message: &Message) -> Context {
+        match self.mode {
+            Mode::SequenceEdit => match message {
+                Message::NoteOn { note_number: n, .. }
+                    if self.shift && self.held_step.is_some() =>
+                {
+                    self.copy_held_steps(note_number_to_pad(*n))
+                }
+                Message::Up if self.shift => {
+                    self.copy(Clipboard::Sequence(self.selected_sequence().clone()))
+                }
+                Message::Down if self.shift => self.paste_into_selected_sequence(),
+                Message::SetLoop => self.change_selected_sequence(Sequence::double),
+                Message::NoteOn {
+                    note_number: n,
+                    velocity: v,
+                } => self.press_step(
+                    Step::on_page(self.displayed_page(), note_number_to_pad(*n)),
+                    *v,
+                ),
+                Message::NoteOff { note_number: n } => self.release_step(note_number_to_pad(*n)),
+                Message::Left => self.change_page(-1),
+                Message::Right => self.change_page(1),
+                Message::Up => Context {
+                    page_follow: true,
+                    ..self.clone()
+                },
+                Message::KnobIncrement { number: 1 } if self.held_step.is_some() => {
+                    self.change_held_step(|s, step| s.nudge_step(step, 1))
+                }
+                Message::KnobDecrement { number: 1 } if self.held_step.is_some() => {
+                    self.change_held_step(|s, step| s.nudge_step(step, -1))
+                }
+                Message::KnobIncrement { number: 2 } if self.held_step.is_some() => {
+                    self.change_held_step(Sequence::increment_ramp)
+                }
+                Message::KnobDecrement { number: 2 } if self.held_step.is_some() => {
+                    self.change_held_step(Sequence::decrement_ramp)
+                }
+                Message::KnobIncrement { number: 3 } if self.held_step.is_some() => {
+                    self.change_held_step(Sequence::increment_ratchets)
+                }
+                Message::KnobDecrement { number: 3 } if self.held_step.is_some() => {
+                    self.change_held_step(Sequence::decrement_ratchets)
+                }
+                Message::KnobIncrement { number: 4 } if self.held_step.is_some() && self.shift => {
+                    self.change_held_step(|s, step| s.change_step_gate(step, 1))
+                }
+                Message::KnobDecrement { number: 4 } if self.held_step.is_some() && self.shift => {
+                    self.change_held_step(|s, step| s.change_step_gate(step, -1))
+                }
+                Message::KnobIncrement { number: 4 } if self.held_step.is_some() => {
+                    self.change_held_step(Sequence::increment_condition)
+                }
+                Message::KnobDecrement { number: 4 } if self.held_step.is_some() => {
+                    self.change_held_step(Sequence::decrement_condition)
+                }
+                Message::KnobIncrement { number: 1 } if self.shift => {
+                    self.change_selected_sequence(Sequence::increment_division)
+                }
+                Message::KnobDecrement { number: 1 } if self.shift => {
+                    self.change_selected_sequence(Sequence::decrement_division)
                 }
                 Message::KnobIncrement { number: 1 } => {
                     self.change_selected_sequence(Sequence::increment_length)
                 }
-                Message::KnobDecrement { number: 1 } => {
-                    self.change_selected_sequence(Sequence::decrement_length)
+                Message::KnobDecrement { number: 1 } => self
+                    .change_selected_sequence(Sequence::decrement_length)
+                    .flush_note_offs(self.selected_sequence),
+                Message::KnobIncrement { number: 2 } if self.shift => {
+                    self.change_selected_sequence(Sequence::increment_swing)
+                }
+                Message::KnobDecrement { number: 2 } if self.shift => {
+                    self.change_selected_sequence(Sequence::decrement_swing)
+                }
+                Message::KnobIncrement { number: 3 } if self.shift => {
+                    self.change_selected_sequence(Sequence::increment_direction)
+                }
+                Message::KnobDecrement { number: 3 } if self.shift => {
+                    self.change_selected_sequence(Sequence::decrement_direction)
                 }
                 Message::KnobIncrement { number: 2 } => {
                     self.change_selected_sequence(Sequence::increment_euclidean_fill)
@@ -174,35 +1300,89 @@ impl Context {
                 Message::KnobDecrement { number: 3 } => {
                     self.change_selected_sequence(Sequence::decrement_rotate)
                 }
+                Message::KnobIncrement { number: 4 } => {
+                    self.change_selected_sequence(Sequence::increment_gate)
+                }
+                Message::KnobDecrement { number: 4 } => {
+                    self.change_selected_sequence(Sequence::decrement_gate)
+                }
+                Message::ShiftOn => Context {
+                    shift: true,
+                    ..self.clone()
+                },
+                Message::ShiftOff => Context {
+                    shift: false,
+                    ..self.clone()
+                },
                 Message::SelectOn => self.set_mode(Mode::Performance),
                 _ => self.clone(),
             },
             Mode::SequenceMute => match message {
-                Message::NoteOn { note_number: n } => {
+                Message::NoteOn { note_number: n, .. } => {
                     self.mute_sequence(note_number_to_sequence(*n))
                 }
                 Message::ShiftOff => self.set_mode(Mode::Performance),
+                Message::KnobIncrement { number: 1 } => self.change_bpm(0.1),
+                Message::KnobDecrement { number: 1 } => self.change_bpm(-0.1),
                 _ => self.clone(),
             },
             Mode::SequenceSelect => match message {
-                Message::NoteOn { note_number: n } => {
+                Message::NoteOn { note_number: n, .. } => {
                     self.select_sequence(note_number_to_sequence(*n))
                 }
                 Message::SelectOff => self.set_mode(Mode::Performance),
                 _ => self.clone(),
             },
+            Mode::PatternSelect => match message {
+                Message::NoteOn { note_number: n, .. } if self.shift => self
+                    .paste_pattern_from_clipboard(
+                        self.bank * pattern::PATTERNS_PER_BANK + note_number_to_sequence(*n),
+                    ),
+                Message::Up => self.copy(Clipboard::Pattern(self.pattern_at(self.pattern))),
+                Message::ShiftOn => Context {
+                    shift: true,
+                    ..self.clone()
+                },
+                Message::ShiftOff => Context {
+                    shift: false,
+                    ..self.clone()
+                },
+                Message::NoteOn { note_number: n, .. } => self.queue_pattern(
+                    self.bank * pattern::PATTERNS_PER_BANK + note_number_to_sequence(*n),
+                ),
+                Message::Left => self.change_bank(-1),
+                Message::Right => self.change_bank(1),
+                Message::BankOff => self.set_mode(Mode::Performance),
+                _ => self.clone(),
+            },
             Mode::Performance => match message {
-                Message::NoteOn { note_number: n } => {
+                Message::NoteOn { note_number: n, .. } => {
                     self.trigger_default_note(note_number_to_sequence(*n))
                 }
                 Message::ShiftOn => self.set_mode(Mode::SequenceMute),
                 Message::SelectOn => self.set_mode(Mode::SequenceSelect),
-                Message::KnobIncrement { number: 1 } => Context {
-                    bpm: (self.bpm + 1.0).min(240.0),
+                Message::BankOn => Context {
+                    bank: pattern::bank(self.pattern),
+                    ..self.set_mode(Mode::PatternSelect)
+                },
+                Message::KnobIncrement { number: 1 } => self.change_bpm(1.0),
+                Message::KnobDecrement { number: 1 } => self.change_bpm(-1.0),
+                Message::Left => self.nudge_tempo(-tempo::NUDGE),
+                Message::Right => self.nudge_tempo(tempo::NUDGE),
+                Message::KnobIncrement { number: 2 } => Context {
+                    swing: self.swing.increment_amount(),
                     ..self.clone()
                 },
-                Message::KnobDecrement { number: 1 } => Context {
-                    bpm: (self.bpm - 1.0).max(30.0),
+                Message::KnobDecrement { number: 2 } => Context {
+                    swing: self.swing.decrement_amount(),
+                    ..self.clone()
+                },
+                Message::KnobIncrement { number: 3 } => Context {
+                    swing: self.swing.set_resolution(Resolution::Eighth),
+                    ..self.clone()
+                },
+                Message::KnobDecrement { number: 3 } => Context {
+                    swing: self.swing.set_resolution(Resolution::Sixteenth),
                     ..self.clone()
                 },
                 _ => self.clone(),
@@ -211,7 +1391,7 @@ impl Context {
     }
 }
 
-fn note_number_to_sixteenth(note_number: i32) -> i32 {
+fn note_number_to_pad(note_number: i32) -> i32 {
     note_number - 35
 }
 
@@ -230,26 +1410,55 @@ fn test_advance_tick() {
 fn test_process_note_on_message_to_toggle_step() {
     // Sequence 3 corresponds to pad 4, mapped to MIDI note 39 by default
     let context = Context::default().select_sequence(3);
-    let messages = vec![Message::NoteOn { note_number: 36 }];
+    let messages = vec![Message::NoteOn {
+        note_number: 36,
+        velocity: 127,
+    }];
 
     let processed_context = context.process_messages(messages);
 
     assert_eq!(39, processed_context.events_for_tick(0)[0].note_number)
 }
 
+#[test]
+fn test_process_note_on_message_sets_step_velocity() {
+    let context = Context::default().select_sequence(0);
+    let messages = vec![Message::NoteOn {
+        note_number: 36,
+        velocity: 42,
+    }];
+
+    let processed_context = context.process_messages(messages);
+
+    assert_eq!(42, processed_context.events_for_tick(0)[0].velocity)
+}
+
 #[test]
 fn test_process_note_on_message_to_select_sequence() {
     let context = Context::default().set_mode(Mode::Performance);
-    let messages = vec![Message::SelectOn, Message::NoteOn { note_number: 43 }];
+    let messages = vec![
+        Message::SelectOn,
+        Message::NoteOn {
+            note_number: 43,
+            velocity: 127,
+        },
+    ];
     let processed_context = context.process_messages(messages);
 
     assert_eq!(7, processed_context.selected_sequence);
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_process_note_on_message_to_mute_sequence() {
     let context = Context::default().set_mode(Mode::Performance);
-    let messages = vec![Message::ShiftOn, Message::NoteOn { note_number: 43 }];
+    let messages = vec![
+        Message::ShiftOn,
+        Message::NoteOn {
+            note_number: 43,
+            velocity: 127,
+        },
+    ];
     let processed_context = context.process_messages(messages);
 
     let muted_sequence = &processed_context.sequences[7];
@@ -284,3 +1493,1147 @@ fn test_clear_performance_events() {
     assert_eq!(1, context.events().len());
     assert_eq!(0, context.clear_performance_events().events().len());
 }
+
+#[test]
+fn test_schedule_note_offs() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs();
+
+    assert_eq!(1, context.pending_note_offs.len());
+    assert_eq!(6, context.pending_note_offs[0].tick);
+    assert_eq!(1, context.pending_note_offs[0].channel);
+
+    let mut context = context;
+    for _ in 0..5 {
+        context = context.advance_tick().schedule_note_offs();
+        assert!(context.note_offs().is_empty());
+    }
+
+    let context = context.advance_tick();
+    assert_eq!(1, context.note_offs().len());
+    assert!(context.schedule_note_offs().pending_note_offs.is_empty());
+}
+
+#[test]
+fn test_retrigger_sends_pending_note_off() {
+    let context = Context::default()
+        .select_sequence(0)
+        .change_selected_sequence(|s| s.set_length(1).set_gate(12))
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs();
+
+    // the one step sequence retriggers after 6 ticks, before the 12 tick
+    // gate has elapsed
+    let mut context = context;
+    for _ in 0..6 {
+        context = context.advance_tick();
+    }
+
+    assert_eq!(1, context.note_offs().len());
+    assert_eq!(1, context.schedule_note_offs().pending_note_offs.len());
+    assert_eq!(18, context.schedule_note_offs().pending_note_offs[0].tick);
+}
+
+#[test]
+fn test_mute_sequence_flushes_note_offs() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs()
+        .advance_tick();
+
+    assert!(context.note_offs().is_empty());
+    assert_eq!(1, context.mute_sequence(0).note_offs().len());
+    assert!(context.mute_sequence(1).note_offs().is_empty());
+}
+
+#[test]
+fn test_shorten_sequence_flushes_note_offs() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs()
+        .advance_tick();
+
+    let processed_context = context.process_messages(vec![Message::KnobDecrement { number: 1 }]);
+    assert_eq!(1, processed_context.note_offs().len());
+}
+
+#[test]
+fn test_press_and_release_step() {
+    let context = Context::default().select_sequence(0);
+    let note_on = Message::NoteOn {
+        note_number: 36,
+        velocity: 127,
+    };
+    let note_off = Message::NoteOff { note_number: 36 };
+
+    let context = context.process_messages(vec![note_on.clone(), note_off.clone()]);
+    assert_eq!(1, context.selected_sequence().active_steps().len());
+    assert!(context.held_step.is_none());
+
+    let held_context = context.process_messages(vec![note_on]);
+    assert_eq!(1, held_context.selected_sequence().active_steps().len());
+    assert!(held_context.held_step.is_some());
+
+    let context = held_context.process_messages(vec![note_off]);
+    assert!(context.selected_sequence().active_steps().is_empty());
+}
+
+#[test]
+fn test_nudge_held_step() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(2);
+    let messages = vec![
+        Message::NoteOn {
+            note_number: 37,
+            velocity: 127,
+        },
+        Message::KnobIncrement { number: 1 },
+        Message::KnobIncrement { number: 1 },
+        Message::NoteOff { note_number: 37 },
+    ];
+
+    let processed_context = context.process_messages(messages);
+
+    assert_eq!(
+        1,
+        processed_context.selected_sequence().active_steps().len()
+    );
+    assert!(processed_context.events_for_tick(6).is_empty());
+    assert_eq!(1, processed_context.events_for_tick(8).len());
+}
+
+#[test]
+fn test_process_knob_2_swing_message() {
+    let context = Context::default().set_mode(Mode::Performance);
+
+    let processed_context = context.process_messages(vec![Message::KnobIncrement { number: 2 }]);
+    assert_eq!(51, processed_context.swing.amount);
+
+    let processed_context = processed_context.process_messages(vec![
+        Message::KnobIncrement { number: 3 },
+        Message::KnobDecrement { number: 2 },
+    ]);
+    assert_eq!(50, processed_context.swing.amount);
+    assert_eq!(Resolution::Eighth, processed_context.swing.resolution);
+}
+
+#[test]
+fn test_swing_delays_off_beat_steps() {
+    let context = Context {
+        swing: Swing::default().with_amount(66),
+        ..Context::default()
+    }
+    .select_sequence(0)
+    .toggle_step_for_selected_sequence(2);
+
+    assert!(context.events_for_tick(6).is_empty());
+    assert_eq!(1, context.events_for_tick(8).len());
+}
+
+#[test]
+fn test_shift_knob_2_sets_sequence_swing() {
+    let context = Context {
+        swing: Swing::default().with_amount(66),
+        ..Context::default()
+    }
+    .select_sequence(0)
+    .toggle_step_for_selected_sequence(2);
+    let messages = vec![
+        Message::ShiftOn,
+        Message::KnobIncrement { number: 2 },
+        Message::ShiftOff,
+    ];
+
+    let processed_context = context.process_messages(messages);
+
+    assert!(!processed_context.shift);
+    assert_eq!(1, processed_context.events_for_tick(6).len());
+    assert_eq!(
+        1,
+        processed_context.selected_sequence().active_steps().len()
+    );
+}
+
+#[test]
+fn test_fill() {
+    let context = Context::default();
+
+    assert!(context.process_messages(vec![Message::FillOn]).fill);
+    assert!(
+        !context
+            .process_messages(vec![Message::FillOn, Message::FillOff])
+            .fill
+    );
+}
+
+#[test]
+fn test_knob_4_changes_held_step_condition() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1);
+
+    // Stepping past Always, the 21 probabilities and the 35 ratios
+    // reaches the Fill condition
+    let mut messages = vec![Message::NoteOn {
+        note_number: 36,
+        velocity: 127,
+    }];
+    for _ in 0..57 {
+        messages.push(Message::KnobIncrement { number: 4 });
+    }
+    messages.push(Message::NoteOff { note_number: 36 });
+
+    let processed_context = context.process_messages(messages);
+
+    assert_eq!(
+        1,
+        processed_context.selected_sequence().active_steps().len()
+    );
+    assert!(processed_context.events().is_empty());
+    assert_eq!(
+        1,
+        processed_context
+            .process_messages(vec![Message::FillOn])
+            .events()
+            .len()
+    );
+}
+
+#[test]
+fn test_shift_knob_4_changes_held_step_gate() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1)
+        .toggle_step_for_selected_sequence(2)
+        .process_messages(vec![
+            Message::NoteOn {
+                note_number: 36,
+                velocity: 127,
+            },
+            Message::ShiftOn,
+            Message::KnobIncrement { number: 4 },
+            Message::KnobIncrement { number: 4 },
+            Message::ShiftOff,
+            Message::NoteOff { note_number: 36 },
+        ]);
+
+    assert_eq!(DEFAULT_LENGTH + 2, context.events_for_tick(0)[0].length);
+    assert_eq!(DEFAULT_LENGTH, context.events_for_tick(6)[0].length);
+
+    // without a held step the knob sets the gate of new steps only
+    let context = context.process_messages(vec![Message::KnobDecrement { number: 4 }]);
+    assert_eq!(DEFAULT_LENGTH + 2, context.events_for_tick(0)[0].length);
+    assert_eq!(
+        DEFAULT_LENGTH - 1,
+        context
+            .toggle_step_for_selected_sequence(3)
+            .events_for_tick(12)[0]
+            .length
+    );
+}
+
+#[test]
+fn test_knob_3_changes_held_step_ratchets() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1);
+    let messages = vec![
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+        Message::KnobIncrement { number: 3 },
+        Message::NoteOff { note_number: 36 },
+    ];
+
+    let processed_context = context.process_messages(messages);
+
+    assert_eq!(1, processed_context.events_for_tick(0).len());
+    assert_eq!(1, processed_context.events_for_tick(3).len());
+}
+
+#[test]
+fn test_shift_knob_1_changes_division() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(2);
+    let messages = vec![
+        Message::ShiftOn,
+        Message::KnobIncrement { number: 1 },
+        Message::KnobIncrement { number: 1 },
+        Message::ShiftOff,
+    ];
+
+    let processed_context = context.process_messages(messages);
+
+    // sixteenth -> eighth triplet -> dotted sixteenth
+    assert!(processed_context.events_for_tick(6).is_empty());
+    assert_eq!(1, processed_context.events_for_tick(9).len());
+}
+
+#[test]
+fn test_change_page() {
+    let context = Context::default()
+        .select_sequence(0)
+        .change_selected_sequence(|s| s.set_length(40));
+
+    assert_eq!(0, context.displayed_page());
+
+    let processed_context = context.process_messages(vec![Message::Right, Message::Right]);
+    assert_eq!(2, processed_context.displayed_page());
+    assert!(!processed_context.page_follow);
+
+    let processed_context = processed_context.process_messages(vec![Message::Right]);
+    assert_eq!(2, processed_context.displayed_page());
+
+    let processed_context = processed_context.process_messages(vec![Message::Left]);
+    assert_eq!(1, processed_context.displayed_page());
+
+    let processed_context = processed_context.process_messages(vec![
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+        Message::NoteOff { note_number: 36 },
+    ]);
+    assert!(processed_context
+        .selected_sequence()
+        .active_steps()
+        .contains(&Step(17)));
+}
+
+#[test]
+fn test_page_follows_playhead() {
+    let context = Context {
+        tick: 6 * 20,
+        ..Context::default()
+    }
+    .select_sequence(0)
+    .change_selected_sequence(|s| s.set_length(32));
+
+    assert_eq!(1, context.displayed_page());
+
+    let processed_context = context.process_messages(vec![Message::Left]);
+    assert_eq!(0, processed_context.displayed_page());
+
+    let processed_context = processed_context.process_messages(vec![Message::Up]);
+    assert_eq!(1, processed_context.displayed_page());
+}
+
+#[test]
+fn test_release_held_step_after_page_change() {
+    let context = Context {
+        tick: 6 * 15,
+        ..Context::default()
+    }
+    .select_sequence(0)
+    .change_selected_sequence(|s| s.set_length(32).toggle_step(Step(2)));
+    let messages = vec![Message::NoteOn {
+        note_number: 37,
+        velocity: 127,
+    }];
+
+    let processed_context = context.process_messages(messages);
+    assert!(processed_context.held_step.is_some());
+
+    let processed_context = Context {
+        tick: 6 * 16,
+        ..processed_context
+    }
+    .process_messages(vec![Message::NoteOff { note_number: 37 }]);
+
+    assert!(processed_context.held_step.is_none());
+    assert!(processed_context
+        .selected_sequence()
+        .active_steps()
+        .is_empty());
+}
+
+#[test]
+fn test_shift_knob_3_changes_direction() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(16);
+    let messages = vec![
+        Message::ShiftOn,
+        Message::KnobIncrement { number: 3 },
+        Message::ShiftOff,
+    ];
+
+    let processed_context = context.process_messages(messages);
+
+    assert_eq!(1, processed_context.events_for_tick(0).len());
+}
+
+#[test]
+fn test_stop_and_play() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .advance_tick()
+        .process_messages(vec![Message::Stop]);
+
+    assert_eq!(Transport::Stopped, context.transport);
+    assert_eq!(0, context.tick);
+    assert!(context.events().is_empty());
+    assert_eq!(0, context.advance_tick().tick);
+
+    let context = context.process_messages(vec![Message::Play]);
+
+    assert_eq!(Transport::Playing, context.transport);
+    assert_eq!(1, context.events().len());
+}
+
+#[test]
+fn test_pause_and_continue() {
+    let context = Context {
+        tick: 10,
+        ..Context::default()
+    }
+    .process_messages(vec![Message::Play]);
+
+    assert_eq!(Transport::Paused, context.transport);
+    assert_eq!(10, context.advance_tick().tick);
+
+    let context = context.process_messages(vec![Message::Continue]);
+
+    assert_eq!(Transport::Playing, context.transport);
+    assert_eq!(11, context.advance_tick().tick);
+}
+
+#[test]
+fn test_performance_events_sound_while_stopped() {
+    let context = Context::default().process_messages(vec![
+        Message::Stop,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+    ]);
+
+    assert_eq!(1, context.events().len());
+}
+
+#[test]
+fn test_stop_flushes_note_offs() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs()
+        .advance_tick()
+        .process_messages(vec![Message::Stop]);
+
+    assert_eq!(1, context.note_offs().len());
+}
+
+#[test]
+fn test_external_transport() {
+    let context = Context::default()
+        .process_messages(vec![Message::Pause, Message::SongPosition { position: 4 }]);
+
+    assert_eq!(Transport::Paused, context.transport);
+    assert_eq!(24, context.tick);
+
+    let context = context.process_messages(vec![Message::ExternalContinue]);
+    assert_eq!(24, context.tick);
+
+    let context = context.process_messages(vec![Message::Start]);
+    assert_eq!(Transport::Playing, context.transport);
+    assert_eq!(0, context.tick);
+}
+
+#[test]
+fn test_first_external_clock_plays_the_position() {
+    let context = Context {
+        transport: Transport::Stopped,
+        ..Context::default()
+    }
+    .toggle_step_for_selected_sequence(1)
+    .toggle_step_for_selected_sequence(5)
+    .next(false, vec![Message::Start])
+    .next(true, vec![Message::Clock]);
+
+    assert_eq!(0, context.tick);
+    assert_eq!(1, context.sequenced_events().len());
+
+    let context = context.next(true, vec![Message::Clock]);
+    assert_eq!(1, context.tick);
+
+    let context = context
+        .next(
+            false,
+            vec![
+                Message::Pause,
+                Message::SongPosition { position: 4 },
+                Message::ExternalContinue,
+            ],
+        )
+        .next(true, vec![Message::Clock]);
+    assert_eq!(24, context.tick);
+    assert_eq!(1, context.sequenced_events().len());
+    assert_eq!(25, context.next(true, vec![Message::Clock]).tick);
+}
+
+#[test]
+fn test_fine_adjust_bpm_with_shift() {
+    let processed_context = Context::default().process_messages(vec![
+        Message::ShiftOn,
+        Message::KnobIncrement { number: 1 },
+        Message::KnobIncrement { number: 1 },
+        Message::ShiftOff,
+    ]);
+
+    assert_eq!(120.2, processed_context.bpm);
+}
+
+#[test]
+fn test_tap_tempo() {
+    let start = Instant::now();
+    let messages = (0..4)
+        .map(|n| Message::Tap {
+            time: start + std::time::Duration::from_millis(400 * n),
+        })
+        .collect();
+
+    let processed_context = Context::default().process_messages(messages);
+
+    assert_eq!(150.0, processed_context.bpm);
+}
+
+#[test]
+fn test_nudge_tempo() {
+    let context = Context::default().process_messages(vec![Message::Right]);
+    assert_eq!(120.0, context.bpm);
+    assert!(context.tempo() > 120.0);
+
+    let context = context.process_messages(vec![Message::RightOff, Message::Left]);
+    assert!(context.tempo() < 120.0);
+
+    let context = context.process_messages(vec![Message::LeftOff]);
+    assert_eq!(120.0, context.tempo());
+}
+
+#[test]
+fn test_nudge_tempo_ends_after_mode_change() {
+    let context = Context::default().process_messages(vec![
+        Message::Right,
+        Message::ShiftOn,
+        Message::RightOff,
+    ]);
+    assert_eq!(120.0, context.tempo());
+
+    let context = Context::default().process_messages(vec![
+        Message::Left,
+        Message::SelectOn,
+        Message::LeftOff,
+        Message::SelectOff,
+    ]);
+    assert_eq!(120.0, context.tempo());
+}
+
+#[test]
+fn test_tempo_ramp() {
+    let mut context = Context::default().process_messages(vec![Message::TempoRamp {
+        bpm: 130.0,
+        bars: 1,
+    }]);
+
+    for _ in 0..48 {
+        context = context.advance_tick();
+    }
+    assert_eq!(125.0, context.bpm);
+
+    for _ in 0..48 {
+        context = context.advance_tick();
+    }
+    assert_eq!(130.0, context.bpm);
+    assert!(context.tempo_ramp.is_none());
+}
+
+#[test]
+fn test_changing_bpm_cancels_tempo_ramp() {
+    let context = Context::default().process_messages(vec![
+        Message::TempoRamp {
+            bpm: 130.0,
+            bars: 4,
+        },
+        Message::KnobIncrement { number: 1 },
+    ]);
+
+    assert!(context.tempo_ramp.is_none());
+    assert_eq!(121.0, context.advance_tick().bpm);
+}
+
+#[test]
+fn test_time_signature() {
+    let context = Context::default().select_sequence(0).process_messages(vec![
+        Message::KnobIncrement { number: 1 },
+        Message::SelectOn,
+        Message::TimeSignature { beats: 7, unit: 8 },
+    ]);
+
+    assert_eq!(84, context.time_signature.ticks_per_bar());
+    assert_eq!(17, context.sequences[0].number_of_steps());
+    assert_eq!(14, context.sequences[1].number_of_steps());
+
+    let context = Context {
+        tick: 90,
+        ..context
+    };
+    assert_eq!(
+        Position {
+            bar: 2,
+            beat: 1,
+            tick: 6
+        },
+        context.position()
+    );
+}
+
+#[test]
+fn test_count_in() {
+    let mut context = Context {
+        transport: Transport::Stopped,
+        ..Context::default()
+    }
+    .toggle_step_for_selected_sequence(1)
+    .process_messages(vec![
+        Message::TimeSignature { beats: 3, unit: 4 },
+        Message::CountIn { on: true },
+        Message::Play,
+    ]);
+
+    assert_eq!(-72, context.tick);
+
+    let mut clicks = vec![];
+    while context.tick < 0 {
+        for event in context.events() {
+            assert_eq!(COUNT_IN_NOTE_NUMBER, event.note_number);
+            clicks.push(context.tick);
+        }
+        context = context.advance_tick();
+    }
+
+    assert_eq!(vec![-72, -48, -24], clicks);
+    assert_eq!(36, context.events()[0].note_number);
+}
+
+#[test]
+fn test_shift_and_setup_saves_project() {
+    let context = Context::default().process_messages(vec![Message::SetupOn]);
+    assert_eq!(None, context.project_request);
+
+    let context = context.process_messages(vec![Message::ShiftOn, Message::SetupOn]);
+    assert_eq!(
+        Some(project::Request::Save { path: None }),
+        context.project_request
+    );
+    assert_eq!(None, context.clear_project_request().project_request);
+}
+
+#[test]
+fn test_project_loaded() {
+    let project = project::Project {
+        bpm: 90.0,
+        ..project::Project::default()
+    };
+    let context = Context::default().process_messages(vec![Message::ProjectLoaded {
+        project: Box::new(project),
+    }]);
+
+    assert_eq!(90.0, context.bpm);
+}
+
+#[test]
+fn test_setup_restores_recovered_project() {
+    let context = Context {
+        recovery_available: true,
+        ..Context::default()
+    }
+    .process_messages(vec![Message::SetupOn]);
+
+    assert_eq!(Some(project::Request::Restore), context.project_request);
+
+    let context = context.process_messages(vec![Message::ProjectLoaded {
+        project: Box::new(project::Project::default()),
+    }]);
+    assert!(!context.recovery_available);
+}
+
+#[test]
+fn test_midi_imported() {
+    let imported = Sequence::with_default_note_number(40).toggle_step(Step(2));
+    let context = Context::default()
+        .select_sequence(3)
+        .toggle_step_for_selected_sequence(1)
+        .process_messages(vec![Message::MidiImported {
+            sequences: vec![imported],
+        }]);
+
+    assert_eq!(1, context.events_for_tick(6).len());
+    assert!(context.sequences[3].active_steps().is_empty());
+}
+
+#[test]
+fn test_queued_pattern_switches_at_end_of_bar() {
+    let mut context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .advance_tick()
+        .process_messages(vec![Message::QueuePattern { bank: 0, number: 1 }]);
+
+    assert_eq!(0, context.pattern);
+    assert_eq!(Some(1), context.queued_pattern);
+
+    while context.tick < 95 {
+        context = context.advance_tick();
+    }
+    assert_eq!(0, context.pattern);
+
+    let context = context.advance_tick();
+    assert_eq!(1, context.pattern);
+    assert_eq!(None, context.queued_pattern);
+    assert_eq!(0, context.pattern_tick());
+    assert!(context.events().is_empty());
+
+    // switching back brings back the first pattern's sequences
+    let context = context.process_messages(vec![
+        Message::Stop,
+        Message::QueuePattern { bank: 0, number: 0 },
+    ]);
+    assert_eq!(0, context.pattern);
+    assert_eq!(1, context.sequences[0].active_steps().len());
+}
+
+#[test]
+fn test_queued_pattern_switches_at_end_of_pattern() {
+    let mut context = Context::default()
+        .change_selected_sequence(|s| s.set_length(4))
+        .process_messages(vec![
+            Message::PatternSwitch {
+                at: pattern::Switch::Pattern,
+            },
+            Message::QueuePattern { bank: 0, number: 1 },
+        ]);
+
+    // the longest sequence is the default 16 steps
+    for _ in 0..95 {
+        context = context.advance_tick();
+    }
+    assert_eq!(0, context.pattern);
+    assert_eq!(1, context.advance_tick().pattern);
+}
+
+#[test]
+fn test_queued_pattern_starts_from_its_beginning() {
+    let mut context = Context::default().process_messages(vec![
+        Message::TimeSignature { beats: 3, unit: 4 },
+        Message::QueuePattern { bank: 0, number: 1 },
+    ]);
+
+    for _ in 0..72 {
+        context = context.advance_tick();
+    }
+    let context = context.toggle_step_for_selected_sequence(1);
+
+    assert_eq!(1, context.pattern);
+    assert_eq!(72, context.tick);
+    assert_eq!(1, context.events().len());
+}
+
+#[test]
+fn test_pattern_tempo() {
+    let context = Context::default().process_messages(vec![
+        Message::Stop,
+        Message::PatternTempo { bpm: Some(90.0) },
+        Message::QueuePattern { bank: 0, number: 1 },
+    ]);
+
+    assert_eq!(90.0, context.bpm);
+
+    let context = context.process_messages(vec![
+        Message::KnobIncrement { number: 1 },
+        Message::QueuePattern { bank: 0, number: 0 },
+    ]);
+    assert_eq!(90.0, context.bpm);
+}
+
+#[test]
+fn test_pattern_select_mode() {
+    let context = Context::default().process_messages(vec![
+        Message::BankOn,
+        Message::Right,
+        Message::NoteOn {
+            note_number: 38,
+            velocity: 127,
+        },
+    ]);
+
+    assert_eq!(Some(18), context.queued_pattern);
+
+    let context = context.process_messages(vec![
+        Message::Left,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+        Message::BankOff,
+    ]);
+
+    assert_eq!(None, context.queued_pattern);
+    assert!(matches!(context.mode, Mode::Performance));
+}
+
+#[cfg(test)]
+fn song_context() -> Context {
+    Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .process_messages(vec![
+            Message::Stop,
+            Message::SongAppend {
+                bank: 0,
+                number: 1,
+                repeats: 2,
+            },
+            Message::SongAppend {
+                bank: 0,
+                number: 0,
+                repeats: 1,
+            },
+            Message::SongMode { on: true },
+            Message::Play,
+        ])
+}
+
+#[test]
+fn test_song_plays_entries_in_order() {
+    let mut context = song_context();
+    assert_eq!(1, context.pattern);
+
+    // the second pattern plays twice through its 16 steps
+    for _ in 0..191 {
+        context = context.advance_tick();
+        assert_eq!(1, context.pattern);
+    }
+
+    let mut context = context.advance_tick();
+    assert_eq!(0, context.pattern);
+    assert_eq!(1, context.song_entry);
+    assert_eq!(1, context.events().len());
+
+    for _ in 0..96 {
+        context = context.advance_tick();
+    }
+    assert_eq!(Transport::Stopped, context.transport);
+}
+
+#[test]
+fn test_song_loop() {
+    let mut context = song_context().process_messages(vec![Message::SongLoop {
+        entries: Some((0, 1)),
+    }]);
+
+    for _ in 0..(192 + 96) {
+        context = context.advance_tick();
+    }
+
+    assert_eq!(Transport::Playing, context.transport);
+    assert_eq!(0, context.song_entry);
+    assert_eq!(1, context.pattern);
+}
+
+#[test]
+fn test_song_jump() {
+    let mut context = song_context()
+        .advance_tick()
+        .process_messages(vec![Message::SongJump { entry: 1 }]);
+
+    assert_eq!(Some(1), context.queued_song_entry);
+
+    // jumps wait for the end of the bar
+    for _ in 0..94 {
+        context = context.advance_tick();
+    }
+    assert_eq!(1, context.pattern);
+
+    let context = context.advance_tick();
+    assert_eq!(0, context.pattern);
+    assert_eq!(1, context.song_entry);
+}
+
+#[test]
+fn test_undo_and_redo_edits() {
+    let context = Context::default().select_sequence(0).process_messages(vec![
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+        Message::NoteOff { note_number: 36 },
+        Message::KnobIncrement { number: 1 },
+    ]);
+    assert_eq!(17, context.selected_sequence().number_of_steps());
+
+    let context = context.process_messages(vec![Message::Undo]);
+    assert_eq!(16, context.selected_sequence().number_of_steps());
+    assert_eq!(1, context.selected_sequence().active_steps().len());
+
+    let context = context.process_messages(vec![Message::Undo, Message::Undo]);
+    assert!(context.selected_sequence().active_steps().is_empty());
+
+    let context = context.process_messages(vec![Message::Redo, Message::Redo]);
+    assert_eq!(17, context.selected_sequence().number_of_steps());
+    assert_eq!(1, context.selected_sequence().active_steps().len());
+}
+
+#[test]
+fn test_undo_with_shift_and_left() {
+    let context = Context::default()
+        .select_sequence(0)
+        .process_messages(vec![Message::KnobIncrement { number: 2 }]);
+    assert_eq!(1, context.selected_sequence().active_steps().len());
+
+    let context = context.process_messages(vec![Message::ShiftOn, Message::Left]);
+    assert!(context.selected_sequence().active_steps().is_empty());
+
+    let context = context.process_messages(vec![Message::Right]);
+    assert_eq!(1, context.selected_sequence().active_steps().len());
+}
+
+#[test]
+fn test_ticks_mutes_and_pattern_switches_are_not_edits() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .advance_tick()
+        .process_messages(vec![
+            Message::ShiftOn,
+            Message::NoteOn {
+                note_number: 36,
+                velocity: 127,
+            },
+            Message::ShiftOff,
+            Message::Stop,
+            Message::QueuePattern { bank: 0, number: 1 },
+        ]);
+
+    assert!(context.history.next_undo().is_none());
+}
+
+#[test]
+fn test_undo_keeps_mutes() {
+    let context = Context::default().select_sequence(0).process_messages(vec![
+        Message::KnobIncrement { number: 2 },
+        Message::SelectOn,
+        Message::ShiftOn,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+        Message::Undo,
+    ]);
+
+    assert!(context.sequences[0].is_muted());
+    assert!(context.sequences[0].active_steps().is_empty());
+}
+
+#[test]
+fn test_undo_edit_to_another_pattern() {
+    let context = Context::default().select_sequence(0).process_messages(vec![
+        Message::Stop,
+        Message::KnobIncrement { number: 2 },
+        Message::QueuePattern { bank: 0, number: 1 },
+    ]);
+    assert_eq!(1, context.patterns[&0].sequences[0].active_steps().len());
+
+    let context = context.process_messages(vec![Message::Undo]);
+    assert_eq!(1, context.pattern);
+    assert!(context.patterns[&0].sequences[0].active_steps().is_empty());
+}
+
+#[test]
+fn test_copy_and_paste_sequence() {
+    let context = Context::default().select_sequence(0).process_messages(vec![
+        Message::KnobIncrement { number: 2 },
+        Message::ShiftOn,
+        Message::Up,
+        Message::ShiftOff,
+        Message::SelectOn,
+        Message::SelectOn,
+        Message::NoteOn {
+            note_number: 38,
+            velocity: 127,
+        },
+        Message::ShiftOn,
+        Message::Down,
+    ]);
+
+    assert_eq!(2, context.selected_sequence);
+    assert_eq!(1, context.sequences[2].active_steps().len());
+    assert_eq!(38, context.events_for_tick(0)[1].note_number);
+}
+
+#[test]
+fn test_copy_sequence_out_of_range() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs();
+    assert_eq!(1, context.pending_note_offs.len());
+
+    let copied = context.process_messages(vec![
+        Message::CopySequence { from: 0, to: 16 },
+        Message::CopySequence { from: 16, to: 0 },
+    ]);
+    assert_eq!(context.sequences, copied.sequences);
+    assert_eq!(context.pending_note_offs, copied.pending_note_offs);
+    assert!(copied.history.next_undo().is_none());
+}
+
+#[test]
+fn test_copy_and_paste_steps() {
+    let press = |n| Message::NoteOn {
+        note_number: n,
+        velocity: 127,
+    };
+    let release = |n| Message::NoteOff { note_number: n };
+
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1)
+        .toggle_step_for_selected_sequence(3)
+        .process_messages(vec![
+            press(36),
+            Message::ShiftOn,
+            press(39),
+            release(39),
+            Message::ShiftOff,
+            release(36),
+            press(44),
+            Message::ShiftOn,
+            Message::Down,
+            Message::ShiftOff,
+            release(44),
+        ]);
+
+    let mut steps: Vec<i32> = context
+        .selected_sequence()
+        .active_steps()
+        .iter()
+        .map(|s| s.0)
+        .collect();
+    steps.sort_unstable();
+    assert_eq!(vec![1, 3, 9, 11], steps);
+}
+
+#[test]
+fn test_double_and_clear() {
+    let context = Context::default()
+        .select_sequence(0)
+        .toggle_step_for_selected_sequence(1)
+        .process_messages(vec![Message::SetLoop]);
+
+    assert_eq!(32, context.selected_sequence().number_of_steps());
+    assert_eq!(2, context.selected_sequence().active_steps().len());
+
+    // shift and stop only stops
+    let stopped = context.process_messages(vec![Message::ShiftOn, Message::Stop]);
+    assert_eq!(context.sequences, stopped.sequences);
+    assert_eq!(Transport::Stopped, stopped.transport);
+
+    let cleared =
+        context.process_messages(vec![Message::FillOn, Message::ShiftOn, Message::SetLoop]);
+    assert!(cleared.selected_sequence().active_steps().is_empty());
+    assert_eq!(32, cleared.selected_sequence().number_of_steps());
+    assert_eq!(Transport::Playing, cleared.transport);
+
+    let undone = cleared.process_messages(vec![Message::Undo]);
+    assert_eq!(context.sequences, undone.sequences);
+}
+
+#[test]
+fn test_clear_all() {
+    let context = Context::default()
+        .set_mode(Mode::Performance)
+        .change_sequence(0, |s| {
+            s.toggle_step_with_velocity(Step(1), 42)
+                .nudge_step(Step(1), 2)
+                .change_step_gate(Step(1), 4)
+        })
+        .change_sequence(5, |s| s.set_length(8).toggle_step(Step(3)));
+
+    let cleared =
+        context.process_messages(vec![Message::FillOn, Message::ShiftOn, Message::SetLoop]);
+
+    // only the steps are cleared
+    let expected: Vec<Sequence> = context.sequences.iter().map(Sequence::clear).collect();
+    assert_eq!(expected, cleared.sequences);
+    assert!(cleared
+        .sequences
+        .iter()
+        .all(|s| s.active_steps().is_empty()));
+    assert_eq!(8, cleared.sequences[5].number_of_steps());
+
+    let undone = cleared.process_messages(vec![Message::Undo]);
+    assert_eq!(context.sequences, undone.sequences);
+}
+
+#[test]
+fn test_copy_pattern() {
+    let context = Context::default().change_sequence(0, |s| {
+        s.toggle_step_with_velocity(Step(1), 42)
+            .nudge_step(Step(1), 2)
+            .change_step_gate(Step(1), 4)
+            .toggle_step(Step(5))
+    });
+
+    let copied = context.process_messages(vec![
+        Message::BankOn,
+        Message::Up,
+        Message::Right,
+        Message::ShiftOn,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127,
+        },
+    ]);
+
+    // the copy has the same triggers as the source, which is unchanged
+    assert_eq!(context.sequences, copied.patterns[&16].sequences);
+    assert_eq!(context.sequences, copied.sequences);
+
+    let overwritten = copied.process_messages(vec![Message::CopyPattern {
+        from: (2, 0),
+        to: (1, 0),
+    }]);
+    assert_eq!(
+        Pattern::default().sequences,
+        overwritten.patterns[&16].sequences
+    );
+    assert_eq!(context.sequences, overwritten.sequences);
+
+    let undone = overwritten.process_messages(vec![Message::Undo]);
+    assert_eq!(context.sequences, undone.patterns[&16].sequences);
+    assert_eq!(context.sequences, undone.sequences);
+
+    let undone = undone.process_messages(vec![Message::Undo]);
+    assert_eq!(Pattern::default().sequences, undone.patterns[&16].sequences);
+}
+
+#[test]
+fn test_performance_note_offs_between_ticks() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .schedule_note_offs()
+        .trigger_default_note(0);
+
+    // the live hit retriggers the sequenced note without scheduling the
+    // sequenced step again
+    assert_eq!(1, context.performance_note_offs().len());
+    let context = context
+        .schedule_performance_note_offs()
+        .clear_performance_events();
+    assert_eq!(1, context.pending_note_offs.len());
+
+    let context = context.pause();
+    assert_eq!(1, context.performance_note_offs().len());
+}
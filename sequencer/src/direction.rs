@@ -0,0 +1,155 @@
+use crate::random;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    // Plays to the end and back again without repeating the first and last
+    // steps, e.g. 1 2 3 4 3 2
+    PingPong,
+    // Plays to the end and back again, repeating the first and last steps,
+    // e.g. 1 2 3 4 4 3 2 1
+    PingPongRepeat,
+    // Swings between the ends of the sequence towards the middle,
+    // e.g. 1 4 2 3
+    Pendulum,
+    Random,
+    // Moves one step forwards or backwards at random
+    RandomWalk,
+}
+
+const ALL: [Direction; 7] = [
+    Direction::Forward,
+    Direction::Reverse,
+    Direction::PingPong,
+    Direction::PingPongRepeat,
+    Direction::Pendulum,
+    Direction::Random,
+    Direction::RandomWalk,
+];
+
+impl Direction {
+    pub fn next(&self) -> Direction {
+        match ALL.iter().position(|d| d == self) {
+            Some(i) if i + 1 < ALL.len() => ALL[i + 1],
+            _ => *self,
+        }
+    }
+
+    pub fn previous(&self) -> Direction {
+        match ALL.iter().position(|d| d == self) {
+            Some(i) if i > 0 => ALL[i - 1],
+            _ => *self,
+        }
+    }
+
+    // The number of positions before the direction repeats itself
+    pub fn loop_length(&self, number_of_steps: i32) -> i32 {
+        match self {
+            Direction::PingPong if number_of_steps > 1 => 2 * number_of_steps - 2,
+            Direction::PingPongRepeat => 2 * number_of_steps,
+            _ => number_of_steps,
+        }
+    }
+
+    // Maps the number of steps played so far to the index (from 0) of the
+    // step to play
+    pub fn index(&self, position: i32, number_of_steps: i32, seed: u64) -> i32 {
+        let n = number_of_steps;
+        let i = position.rem_euclid(self.loop_length(n));
+
+        match self {
+            Direction::Forward => i,
+            Direction::Reverse => n - 1 - i,
+            Direction::PingPong => match i < n {
+                true => i,
+                false => 2 * n - 2 - i,
+            },
+            Direction::PingPongRepeat => match i < n {
+                true => i,
+                false => 2 * n - 1 - i,
+            },
+            Direction::Pendulum => match i % 2 {
+                0 => i / 2,
+                _ => n - 1 - i / 2,
+            },
+            Direction::Random => (random::random(seed, &[position]) % n as u64) as i32,
+            Direction::RandomWalk => {
+                let loop_start = position - i;
+                let start = (random::random(seed, &[loop_start]) % n as u64) as i32;
+
+                ((loop_start + 1)..=position).fold(start, |index, p| {
+                    match random::random(seed, &[p]) % 2 {
+                        0 => (index + 1).rem_euclid(n),
+                        _ => (index - 1).rem_euclid(n),
+                    }
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn indexes(direction: Direction, positions: i32, number_of_steps: i32) -> Vec<i32> {
+    (0..positions)
+        .map(|p| direction.index(p, number_of_steps, 0))
+        .collect()
+}
+
+#[test]
+fn test_forward_and_reverse() {
+    assert_eq!(vec![0, 1, 2, 3, 0, 1], indexes(Direction::Forward, 6, 4));
+    assert_eq!(vec![3, 2, 1, 0, 3, 2], indexes(Direction::Reverse, 6, 4));
+}
+
+#[test]
+fn test_ping_pong() {
+    assert_eq!(
+        vec![0, 1, 2, 3, 2, 1, 0, 1],
+        indexes(Direction::PingPong, 8, 4)
+    );
+    assert_eq!(vec![0, 0, 0], indexes(Direction::PingPong, 3, 1));
+}
+
+#[test]
+fn test_ping_pong_repeat() {
+    assert_eq!(
+        vec![0, 1, 2, 3, 3, 2, 1, 0, 0, 1],
+        indexes(Direction::PingPongRepeat, 10, 4)
+    );
+}
+
+#[test]
+fn test_pendulum() {
+    assert_eq!(vec![0, 4, 1, 3, 2, 0], indexes(Direction::Pendulum, 6, 5));
+}
+
+#[test]
+fn test_random() {
+    let random_indexes = indexes(Direction::Random, 100, 8);
+
+    assert!(random_indexes.iter().all(|i| *i >= 0 && *i < 8));
+    assert_eq!(random_indexes, indexes(Direction::Random, 100, 8));
+    assert_ne!(random_indexes, indexes(Direction::Forward, 100, 8));
+}
+
+#[test]
+fn test_random_walk() {
+    let walk = indexes(Direction::RandomWalk, 100, 8);
+
+    for p in 1..100 {
+        if p % 8 != 0 {
+            let distance = (walk[p] - walk[p - 1]).rem_euclid(8);
+            assert!(distance == 1 || distance == 7);
+        }
+    }
+}
+
+#[test]
+fn test_next_and_previous() {
+    assert_eq!(Direction::Reverse, Direction::Forward.next());
+    assert_eq!(Direction::Forward, Direction::Forward.previous());
+    assert_eq!(Direction::RandomWalk, Direction::RandomWalk.next());
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+// The number of ticks (at 24 ticks per quarter note) in each step of a
+// sequence
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Division(pub i32);
+
+const TICKS_PER_SIXTEENTH: i32 = 6;
+
+impl Division {
+    pub fn thirty_second() -> Division {
+        Division(3)
+    }
+
+    pub fn sixteenth() -> Division {
+        Division(TICKS_PER_SIXTEENTH)
+    }
+
+    pub fn sixteenth_triplet() -> Division {
+        Division(4)
+    }
+
+    pub fn dotted_sixteenth() -> Division {
+        Division(9)
+    }
+
+    pub fn eighth() -> Division {
+        Division(12)
+    }
+
+    pub fn eighth_triplet() -> Division {
+        Division(8)
+    }
+
+    pub fn dotted_eighth() -> Division {
+        Division(18)
+    }
+
+    pub fn quarter() -> Division {
+        Division(24)
+    }
+
+    pub fn quarter_triplet() -> Division {
+        Division(16)
+    }
+
+    pub fn dotted_quarter() -> Division {
+        Division(36)
+    }
+
+    pub fn half() -> Division {
+        Division(48)
+    }
+
+    pub fn whole() -> Division {
+        Division(96)
+    }
+
+    // Divides the sixteenth note clock by n, so each step lasts n sixteenths
+    pub fn clock_divider(n: i32) -> Division {
+        Division(TICKS_PER_SIXTEENTH * n.max(1))
+    }
+
+    // Multiplies the sixteenth note clock by n. Only multipliers that divide
+    // a sixteenth into a whole number of ticks (2, 3 and 6) are exact.
+    pub fn clock_multiplier(n: i32) -> Division {
+        Division((TICKS_PER_SIXTEENTH / n.max(1)).max(1))
+    }
+
+    pub fn ticks_per_step(&self) -> i32 {
+        self.0
+    }
+
+    pub fn next(&self) -> Division {
+        match Division::all().iter().find(|d| d.0 > self.0) {
+            Some(d) => *d,
+            None => *self,
+        }
+    }
+
+    pub fn previous(&self) -> Division {
+        match Division::all().iter().rev().find(|d| d.0 < self.0) {
+            Some(d) => *d,
+            None => *self,
+        }
+    }
+
+    fn all() -> Vec<Division> {
+        let mut divisions = vec![
+            Division::clock_multiplier(6),
+            Division::clock_multiplier(3),
+            Division::thirty_second(),
+            Division::sixteenth_triplet(),
+            Division::eighth_triplet(),
+            Division::dotted_sixteenth(),
+            Division::quarter_triplet(),
+            Division::dotted_eighth(),
+        ];
+
+        for n in 1..=16 {
+            divisions.push(Division::clock_divider(n));
+        }
+
+        divisions.sort_by_key(|d| d.0);
+        divisions.dedup();
+        divisions
+    }
+}
+
+impl Default for Division {
+    fn default() -> Division {
+        Division::sixteenth()
+    }
+}
+
+#[test]
+fn test_clock_divider_and_multiplier() {
+    assert_eq!(Division::eighth(), Division::clock_divider(2));
+    assert_eq!(Division::whole(), Division::clock_divider(16));
+    assert_eq!(Division::thirty_second(), Division::clock_multiplier(2));
+    assert_eq!(Division(1), Division::clock_multiplier(6));
+}
+
+#[test]
+fn test_next_and_previous() {
+    assert_eq!(Division::eighth_triplet(), Division::sixteenth().next());
+    assert_eq!(
+        Division::sixteenth_triplet(),
+        Division::sixteenth().previous()
+    );
+    assert_eq!(Division::dotted_quarter(), Division(30).next());
+    assert_eq!(Division::whole(), Division::whole().next());
+    assert_eq!(Division(1), Division(1).previous());
+}
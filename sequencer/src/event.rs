@@ -1,13 +1,22 @@
 use rosc::OscMessage;
 
+pub const DEFAULT_VELOCITY: i32 = 127;
+pub const DEFAULT_LENGTH: i32 = 6;
+
 #[derive(Clone, Copy, Debug, Eq)]
 pub struct Event {
     pub note_number: i32,
+    pub velocity: i32,
+    pub length: i32,
+    pub channel: i32,
 }
 
 impl PartialEq for Event {
     fn eq(&self, other: &Self) -> bool {
         self.note_number == other.note_number
+            && self.velocity == other.velocity
+            && self.length == other.length
+            && self.channel == other.channel
     }
 }
 
@@ -15,7 +24,88 @@ impl Event {
     pub fn to_osc_message(&self) -> OscMessage {
         OscMessage {
             addr: "/sampler".to_string(),
-            args: vec![rosc::OscType::Int(self.note_number)],
+            args: vec![
+                rosc::OscType::Int(self.note_number),
+                rosc::OscType::Int(self.velocity),
+            ],
         }
     }
+
+    pub fn to_note_on_message(&self, port: &str) -> OscMessage {
+        OscMessage {
+            addr: format!("/{}/note_on", port),
+            args: vec![
+                rosc::OscType::Int(self.channel),
+                rosc::OscType::Int(self.note_number),
+                rosc::OscType::Int(self.velocity),
+            ],
+        }
+    }
+
+    pub fn note_off(&self, tick: i32) -> NoteOff {
+        NoteOff {
+            note_number: self.note_number,
+            channel: self.channel,
+            tick: tick + self.length,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoteOff {
+    pub note_number: i32,
+    pub channel: i32,
+    pub tick: i32,
+}
+
+impl NoteOff {
+    pub fn to_osc_message(&self, port: &str) -> OscMessage {
+        OscMessage {
+            addr: format!("/{}/note_off", port),
+            args: vec![
+                rosc::OscType::Int(self.channel),
+                rosc::OscType::Int(self.note_number),
+                rosc::OscType::Int(0),
+            ],
+        }
+    }
+}
+
+#[test]
+fn test_to_osc_message() {
+    let message = Event {
+        note_number: 36,
+        velocity: 100,
+        length: DEFAULT_LENGTH,
+        channel: 1,
+    }
+    .to_osc_message();
+
+    assert_eq!("/sampler", message.addr);
+    assert_eq!(rosc::OscType::Int(36), message.args[0]);
+    assert_eq!(rosc::OscType::Int(100), message.args[1]);
+}
+
+#[test]
+fn test_note_on_and_note_off_messages() {
+    let event = Event {
+        note_number: 60,
+        velocity: 100,
+        length: 3,
+        channel: 2,
+    };
+
+    let message = event.to_note_on_message("volca");
+    assert_eq!("/volca/note_on", message.addr);
+    assert_eq!(rosc::OscType::Int(2), message.args[0]);
+    assert_eq!(rosc::OscType::Int(60), message.args[1]);
+    assert_eq!(rosc::OscType::Int(100), message.args[2]);
+
+    let note_off = event.note_off(10);
+    assert_eq!(13, note_off.tick);
+
+    let message = note_off.to_osc_message("volca");
+    assert_eq!("/volca/note_off", message.addr);
+    assert_eq!(rosc::OscType::Int(2), message.args[0]);
+    assert_eq!(rosc::OscType::Int(60), message.args[1]);
 }
@@ -0,0 +1,142 @@
+use crate::sequence::Sequence;
+use std::sync::Arc;
+
+// Enough to back out of a long run of knob turns
+pub const MAX_EDITS: usize = 100;
+
+// A pattern's sequences as they were before or after an edit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub pattern: usize,
+    pub sequences: Vec<Sequence>,
+}
+
+// The edits that can be undone and redone, most recent last. They are shared
+// so that cloning the context each tick doesn't copy them.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Arc<Edit>>,
+    redo: Vec<Arc<Edit>>,
+}
+
+impl Edit {
+    // Muting is part of a performance rather than an edit, so undoing keeps
+    // the sequences muted or not as they are now
+    pub fn sequences_keeping_mutes(&self, current: &[Sequence]) -> Vec<Sequence> {
+        self.sequences
+            .iter()
+            .enumerate()
+            .map(|(n, s)| match current.get(n) {
+                Some(c) => s.set_mute(c.is_muted()),
+                None => s.clone(),
+            })
+            .collect()
+    }
+}
+
+impl History {
+    // A new edit can't be redone past, so it clears the redo history
+    pub fn record(&self, edit: Edit) -> History {
+        let mut undo = self.undo.clone();
+        undo.push(Arc::new(edit));
+
+        let excess = undo.len().saturating_sub(MAX_EDITS);
+
+        History {
+            undo: undo.split_off(excess),
+            redo: vec![],
+        }
+    }
+
+    pub fn next_undo(&self) -> Option<Arc<Edit>> {
+        self.undo.last().cloned()
+    }
+
+    pub fn next_redo(&self) -> Option<Arc<Edit>> {
+        self.redo.last().cloned()
+    }
+
+    // Takes the next undo, keeping the state it replaces to redo
+    pub fn undo(&self, current: Edit) -> History {
+        let mut history = self.clone();
+        history.undo.pop();
+        history.redo.push(Arc::new(current));
+        history
+    }
+
+    pub fn redo(&self, current: Edit) -> History {
+        let mut history = self.clone();
+        history.redo.pop();
+        history.undo.push(Arc::new(current));
+        history
+    }
+}
+
+// Only changes other than muting count as edits
+pub fn is_edit(before: &[Sequence], after: &[Sequence]) -> bool {
+    before.len() != after.len()
+        || before
+            .iter()
+            .zip(after)
+            .any(|(b, a)| b != a && b.set_mute(a.is_muted()) != *a)
+}
+
+#[cfg(test)]
+fn edit(steps: &[i32]) -> Edit {
+    use crate::sequence::Step;
+
+    let sequence = steps
+        .iter()
+        .fold(Sequence::empty(), |s, n| s.toggle_step(Step(*n)));
+
+    Edit {
+        pattern: 0,
+        sequences: vec![sequence],
+    }
+}
+
+#[test]
+fn test_undo_and_redo() {
+    let history = History::default().record(edit(&[])).record(edit(&[1]));
+
+    assert_eq!(edit(&[1]), *history.next_undo().unwrap());
+
+    let history = history.undo(edit(&[1, 2]));
+    assert_eq!(edit(&[]), *history.next_undo().unwrap());
+    assert_eq!(edit(&[1, 2]), *history.next_redo().unwrap());
+
+    let history = history.redo(edit(&[1]));
+    assert_eq!(edit(&[1]), *history.next_undo().unwrap());
+    assert!(history.next_redo().is_none());
+}
+
+#[test]
+fn test_record_clears_redo() {
+    let history = History::default()
+        .record(edit(&[]))
+        .undo(edit(&[1]))
+        .record(edit(&[]));
+
+    assert!(history.next_redo().is_none());
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let mut history = History::default();
+    for _ in 0..(MAX_EDITS + 10) {
+        history = history.record(edit(&[]));
+    }
+
+    assert_eq!(MAX_EDITS, history.undo.len());
+}
+
+#[test]
+fn test_muting_is_not_an_edit() {
+    let before = edit(&[1]).sequences;
+    let muted: Vec<Sequence> = before.iter().map(|s| s.toggle_mute()).collect();
+
+    assert!(!is_edit(&before, &before));
+    assert!(!is_edit(&before, &muted));
+    assert!(is_edit(&before, &edit(&[]).sequences));
+    assert!(is_edit(&muted, &edit(&[]).sequences));
+}
@@ -1,22 +1,157 @@
+use crate::event::DEFAULT_VELOCITY;
+use crate::pattern;
+use crate::project::Project;
+use crate::sequence::{Sequence, Step, MAX_STEPS};
 use rosc::OscPacket;
 use std::net::UdpSocket;
+use std::time::Instant;
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    NoteOn { note_number: i32 },
-    NoteOff { note_number: i32 },
-    KnobIncrement { number: i32 },
-    KnobDecrement { number: i32 },
+    NoteOn {
+        note_number: i32,
+        velocity: i32,
+    },
+    NoteOff {
+        note_number: i32,
+    },
+    KnobIncrement {
+        number: i32,
+    },
+    KnobDecrement {
+        number: i32,
+    },
     Left,
+    LeftOff,
     Right,
+    RightOff,
     SelectOn,
     SelectOff,
     Up,
+    Down,
+    SetLoop,
     ShiftOn,
     ShiftOff,
+    FillOn,
+    FillOff,
+    Play,
+    Stop,
+    Continue,
+    // Transport and clock from the device whose MIDI clock is followed
+    Start,
+    Pause,
+    ExternalContinue,
+    SongPosition {
+        position: i32,
+    },
+    Clock,
+    Tap {
+        time: Instant,
+    },
+    TempoRamp {
+        bpm: f32,
+        bars: i32,
+    },
+    TimeSignature {
+        beats: i32,
+        unit: i32,
+    },
+    CountIn {
+        on: bool,
+    },
+    SetupOn,
+    EditorOn,
+    BankOn,
+    BankOff,
+    QueuePattern {
+        bank: usize,
+        number: usize,
+    },
+    PatternSwitch {
+        at: pattern::Switch,
+    },
+    PatternTempo {
+        bpm: Option<f32>,
+    },
+    SongAppend {
+        bank: usize,
+        number: usize,
+        repeats: i32,
+    },
+    SongRemove {
+        entry: usize,
+    },
+    SongClear,
+    SongLoop {
+        entries: Option<(usize, usize)>,
+    },
+    SongMode {
+        on: bool,
+    },
+    SongJump {
+        entry: usize,
+    },
+    Undo,
+    Redo,
+    CopySequence {
+        from: usize,
+        to: usize,
+    },
+    CopySteps {
+        sequence: usize,
+        start: Step,
+        end: Step,
+        destination: Step,
+    },
+    DoubleSequence {
+        sequence: usize,
+    },
+    ClearSequence {
+        sequence: usize,
+    },
+    ClearAll,
+    CopyPattern {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    Save {
+        path: Option<String>,
+    },
+    Load {
+        path: Option<String>,
+    },
+    Restore,
+    ExportMidi {
+        path: Option<String>,
+    },
+    ImportMidi {
+        path: Option<String>,
+        ticks_per_step: Option<i32>,
+        by_track: bool,
+    },
+    MidiImported {
+        sequences: Vec<Sequence>,
+    },
+    ProjectLoaded {
+        project: Box<Project>,
+    },
     Unhandled,
 }
 
+// Clock and transport messages from other devices are only followed with an
+// external clock, so that they can't stop or move the sequencer while it is
+// the clock source
+pub fn is_external_clock(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::Clock
+            | Message::Start
+            | Message::Pause
+            | Message::ExternalContinue
+            | Message::SongPosition { .. }
+    )
+}
+
 pub fn process_incoming_message(sock: &UdpSocket) -> Option<Message> {
     let mut buf = [0u8; rosc::decoder::MTU];
 
@@ -39,9 +174,187 @@ pub fn process_incoming_message(sock: &UdpSocket) -> Option<Message> {
 fn parse_incoming_osc_message(packet: OscPacket) -> Message {
     match packet {
         OscPacket::Message(msg) => {
-            if msg.addr.contains("note_on") {
-                match msg.args[0] {
-                    rosc::OscType::Int(i) => Message::NoteOn { note_number: i },
+            if msg.addr.starts_with("/brain/") {
+                match msg.addr.as_str() {
+                    "/brain/play" => Message::Play,
+                    "/brain/stop" => Message::Stop,
+                    "/brain/continue" => Message::Continue,
+                    "/brain/save" => Message::Save {
+                        path: string_argument(&msg.args),
+                    },
+                    "/brain/load" => Message::Load {
+                        path: string_argument(&msg.args),
+                    },
+                    "/brain/restore" => Message::Restore,
+                    "/brain/export_midi" => Message::ExportMidi {
+                        path: string_argument(&msg.args),
+                    },
+                    "/brain/import_midi" => parse_import_midi(&msg.args),
+                    // Banks and patterns count from 1, like the pads
+                    "/brain/pattern" => match msg.args.as_slice() {
+                        [rosc::OscType::Int(bank), rosc::OscType::Int(number)]
+                            if *bank >= 1 && *number >= 1 =>
+                        {
+                            Message::QueuePattern {
+                                bank: *bank as usize - 1,
+                                number: *number as usize - 1,
+                            }
+                        }
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/pattern_switch" => match string_argument(&msg.args).as_deref() {
+                        Some("bar") => Message::PatternSwitch {
+                            at: pattern::Switch::Bar,
+                        },
+                        Some("pattern") => Message::PatternSwitch {
+                            at: pattern::Switch::Pattern,
+                        },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/pattern_tempo" => match msg.args.as_slice() {
+                        [rosc::OscType::Float(bpm)] => Message::PatternTempo { bpm: Some(*bpm) },
+                        [rosc::OscType::Int(bpm)] => Message::PatternTempo {
+                            bpm: Some(*bpm as f32),
+                        },
+                        [] => Message::PatternTempo { bpm: None },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/song_append" => match msg.args.as_slice() {
+                        [rosc::OscType::Int(bank), rosc::OscType::Int(number), repeats @ ..]
+                            if *bank >= 1 && *number >= 1 =>
+                        {
+                            Message::SongAppend {
+                                bank: *bank as usize - 1,
+                                number: *number as usize - 1,
+                                repeats: match repeats {
+                                    [rosc::OscType::Int(repeats)] => *repeats,
+                                    _ => 1,
+                                },
+                            }
+                        }
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/copy_sequence" => match index_arguments(&msg.args).as_deref() {
+                        Some([from, to]) => Message::CopySequence {
+                            from: *from,
+                            to: *to,
+                        },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/copy_steps" => match index_arguments(&msg.args).as_deref() {
+                        Some([sequence, start, end, destination]) => Message::CopySteps {
+                            sequence: *sequence,
+                            start: step_argument(*start),
+                            end: step_argument(*end),
+                            destination: step_argument(*destination),
+                        },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/double_sequence" => match index_arguments(&msg.args).as_deref() {
+                        Some([sequence]) => Message::DoubleSequence {
+                            sequence: *sequence,
+                        },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/clear_sequence" => match index_arguments(&msg.args).as_deref() {
+                        Some([sequence]) => Message::ClearSequence {
+                            sequence: *sequence,
+                        },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/clear_all" => Message::ClearAll,
+                    "/brain/copy_pattern" => match index_arguments(&msg.args).as_deref() {
+                        Some([from_bank, from_number, to_bank, to_number]) => {
+                            Message::CopyPattern {
+                                from: (*from_bank, *from_number),
+                                to: (*to_bank, *to_number),
+                            }
+                        }
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/song_remove" => match index_arguments(&msg.args).as_deref() {
+                        Some([entry]) => Message::SongRemove { entry: *entry },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/song_clear" => Message::SongClear,
+                    "/brain/undo" => Message::Undo,
+                    "/brain/redo" => Message::Redo,
+                    "/brain/song_loop" => match index_arguments(&msg.args).as_deref() {
+                        Some([start, end]) => Message::SongLoop {
+                            entries: Some((*start, *end)),
+                        },
+                        Some([]) => Message::SongLoop { entries: None },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/song_mode" => match msg.args.as_slice() {
+                        [rosc::OscType::Int(on)] => Message::SongMode { on: *on != 0 },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/song_jump" => match index_arguments(&msg.args).as_deref() {
+                        Some([entry]) => Message::SongJump { entry: *entry },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/tap" => Message::Tap {
+                        time: Instant::now(),
+                    },
+                    "/brain/time_signature" => match msg.args.as_slice() {
+                        [rosc::OscType::Int(beats), rosc::OscType::Int(unit)] => {
+                            Message::TimeSignature {
+                                beats: *beats,
+                                unit: *unit,
+                            }
+                        }
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/count_in" => match msg.args.as_slice() {
+                        [rosc::OscType::Int(on)] => Message::CountIn { on: *on != 0 },
+                        _ => Message::Unhandled,
+                    },
+                    "/brain/tempo_ramp" => match msg.args.as_slice() {
+                        [rosc::OscType::Float(bpm), rosc::OscType::Int(bars)] => {
+                            Message::TempoRamp {
+                                bpm: *bpm,
+                                bars: *bars,
+                            }
+                        }
+                        [rosc::OscType::Int(bpm), rosc::OscType::Int(bars)] => Message::TempoRamp {
+                            bpm: *bpm as f32,
+                            bars: *bars,
+                        },
+                        _ => Message::Unhandled,
+                    },
+                    _ => Message::Unhandled,
+                }
+            } else if msg.addr.ends_with("/clock") {
+                Message::Clock
+            } else if msg.addr.ends_with("/start") {
+                Message::Start
+            } else if msg.addr.ends_with("/stop") {
+                // MIDI Stop keeps the song position, so a following Continue
+                // resumes from where it stopped
+                Message::Pause
+            } else if msg.addr.ends_with("/continue") {
+                Message::ExternalContinue
+            } else if msg.addr.ends_with("/song_position") {
+                match msg.args.as_slice() {
+                    [rosc::OscType::Int(position)] => Message::SongPosition {
+                        position: *position,
+                    },
+                    _ => Message::Unhandled,
+                }
+            } else if msg.addr.contains("note_on") {
+                match msg.args.as_slice() {
+                    [rosc::OscType::Int(n), rosc::OscType::Int(0)] => {
+                        Message::NoteOff { note_number: *n }
+                    }
+                    [rosc::OscType::Int(n), rosc::OscType::Int(v)] => Message::NoteOn {
+                        note_number: *n,
+                        velocity: *v,
+                    },
+                    [rosc::OscType::Int(n)] => Message::NoteOn {
+                        note_number: *n,
+                        velocity: DEFAULT_VELOCITY,
+                    },
                     _ => Message::Unhandled,
                 }
             } else if msg.addr.contains("note_off") {
@@ -54,10 +367,18 @@ fn parse_incoming_osc_message(packet: OscPacket) -> Message {
                     [rosc::OscType::Int(c), rosc::OscType::Int(v)] => {
                         if *c == 90 && *v == 127 {
                             Message::Left
+                        } else if *c == 90 && *v == 0 {
+                            Message::LeftOff
                         } else if *c == 102 && *v == 127 {
                             Message::Right
+                        } else if *c == 102 && *v == 0 {
+                            Message::RightOff
                         } else if *c == 87 && *v == 127 {
                             Message::Up
+                        } else if *c == 89 && *v == 127 {
+                            Message::Down
+                        } else if *c == 85 && *v == 127 {
+                            Message::SetLoop
                         } else if *c == 103 && *v == 127 {
                             Message::SelectOn
                         } else if *c == 103 && *v == 0 {
@@ -66,6 +387,26 @@ fn parse_incoming_osc_message(packet: OscPacket) -> Message {
                             Message::ShiftOn
                         } else if *c == 32 && *v == 0 {
                             Message::ShiftOff
+                        } else if *c == 26 && *v == 127 {
+                            Message::BankOn
+                        } else if *c == 26 && *v == 0 {
+                            Message::BankOff
+                        } else if *c == 24 && *v == 127 {
+                            Message::FillOn
+                        } else if *c == 24 && *v == 0 {
+                            Message::FillOff
+                        } else if *c == 109 && *v == 127 {
+                            Message::Play
+                        } else if *c == 111 && *v == 127 {
+                            Message::Stop
+                        } else if *c == 86 && *v == 127 {
+                            Message::SetupOn
+                        } else if *c == 31 && *v == 127 {
+                            Message::EditorOn
+                        } else if *c == 105 && *v == 127 {
+                            Message::Tap {
+                                time: Instant::now(),
+                            }
                         } else if *c >= 14 && *c <= 17 && *v == 1 {
                             Message::KnobIncrement { number: c - 13 }
                         } else if *c >= 14 && *c <= 17 && *v == 65 {
@@ -84,6 +425,53 @@ fn parse_incoming_osc_message(packet: OscPacket) -> Message {
     }
 }
 
+fn string_argument(args: &[rosc::OscType]) -> Option<String> {
+    match args {
+        [rosc::OscType::String(s)] => Some(s.clone()),
+        _ => None,
+    }
+}
+
+// Sequences, steps, song entries and so on count from 1 over OSC
+fn index_arguments(args: &[rosc::OscType]) -> Option<Vec<usize>> {
+    args.iter()
+        .map(|arg| match arg {
+            rosc::OscType::Int(entry) if *entry >= 1 => Some(*entry as usize - 1),
+            _ => None,
+        })
+        .collect()
+}
+
+// Steps past the longest sequence are clamped to the last step
+fn step_argument(index: usize) -> Step {
+    Step(index.min(MAX_STEPS as usize - 1) as i32 + 1)
+}
+
+// Takes an optional path, grid in ticks per step and "track" to import one
+// sequence per track rather than per note, in any order. A grid of less than
+// a tick isn't handled.
+fn parse_import_midi(args: &[rosc::OscType]) -> Message {
+    let mut path = None;
+    let mut ticks_per_step = None;
+    let mut by_track = false;
+
+    for arg in args {
+        match arg {
+            rosc::OscType::String(s) if s == "track" => by_track = true,
+            rosc::OscType::String(s) => path = Some(s.clone()),
+            rosc::OscType::Int(i) if *i >= 1 => ticks_per_step = Some(*i),
+            rosc::OscType::Int(_) => return Message::Unhandled,
+            _ => (),
+        }
+    }
+
+    Message::ImportMidi {
+        path,
+        ticks_per_step,
+        by_track,
+    }
+}
+
 #[cfg(test)]
 use rosc::OscMessage;
 
@@ -94,7 +482,36 @@ fn test_parse_incoming_note_on_message() {
         args: vec![rosc::OscType::Int(36)],
     });
     let msg = parse_incoming_osc_message(packet);
-    assert!(matches!(msg, Message::NoteOn { note_number: 36 }));
+    assert!(matches!(
+        msg,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 127
+        }
+    ));
+}
+
+#[test]
+fn test_parse_incoming_note_on_message_with_velocity() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/10/note_on".to_string(),
+        args: vec![rosc::OscType::Int(36), rosc::OscType::Int(64)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(
+        msg,
+        Message::NoteOn {
+            note_number: 36,
+            velocity: 64
+        }
+    ));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/10/note_on".to_string(),
+        args: vec![rosc::OscType::Int(36), rosc::OscType::Int(0)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::NoteOff { note_number: 36 }));
 }
 
 #[test]
@@ -174,6 +591,23 @@ fn test_parse_incoming_shift_off_message() {
     assert!(matches!(msg, Message::ShiftOff));
 }
 
+#[test]
+fn test_parse_incoming_fill_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(24), rosc::OscType::Int(127)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::FillOn));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(24), rosc::OscType::Int(0)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::FillOff));
+}
+
 #[test]
 fn test_parse_incoming_knob_control_change() {
     let packet = OscPacket::Message(OscMessage {
@@ -198,3 +632,281 @@ fn test_parse_incoming_knob_control_change() {
     let msg = parse_incoming_osc_message(packet);
     assert!(matches!(msg, Message::KnobIncrement { number: 2 }));
 }
+
+#[test]
+fn test_parse_incoming_transport_messages() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(109), rosc::OscType::Int(127)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Play));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(111), rosc::OscType::Int(127)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Stop));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/continue".to_string(),
+        args: vec![],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Continue));
+}
+
+#[test]
+fn test_parse_incoming_external_clock_messages() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/tr-8/clock".to_string(),
+        args: vec![],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Clock));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/tr-8/stop".to_string(),
+        args: vec![],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Pause));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/tr-8/song_position".to_string(),
+        args: vec![rosc::OscType::Int(16)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::SongPosition { position: 16 }));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/tr-8/continue".to_string(),
+        args: vec![],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::ExternalContinue));
+}
+
+#[test]
+fn test_is_external_clock() {
+    let parse = |addr: &str| {
+        parse_incoming_osc_message(OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args: vec![rosc::OscType::Int(16)],
+        }))
+    };
+
+    for addr in &[
+        "/midi/tr-8/clock",
+        "/midi/tr-8/start",
+        "/midi/tr-8/stop",
+        "/midi/tr-8/continue",
+        "/midi/tr-8/song_position",
+    ] {
+        assert!(is_external_clock(&parse(addr)));
+    }
+
+    // the sequencer's own transport is followed with an internal clock
+    assert!(!is_external_clock(&parse("/brain/continue")));
+    assert!(!is_external_clock(&parse("/brain/stop")));
+}
+
+#[test]
+fn test_parse_incoming_tap_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(105), rosc::OscType::Int(127)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Tap { .. }));
+}
+
+#[test]
+fn test_parse_incoming_left_and_right_release() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(90), rosc::OscType::Int(0)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::LeftOff));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/midi/atom/1/1/control_change".to_string(),
+        args: vec![rosc::OscType::Int(102), rosc::OscType::Int(0)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::RightOff));
+}
+
+#[test]
+fn test_parse_incoming_tempo_ramp_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/tempo_ramp".to_string(),
+        args: vec![rosc::OscType::Float(140.5), rosc::OscType::Int(8)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::TempoRamp { bars: 8, .. }));
+}
+
+#[test]
+fn test_parse_incoming_time_signature_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/time_signature".to_string(),
+        args: vec![rosc::OscType::Int(7), rosc::OscType::Int(8)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::TimeSignature { beats: 7, unit: 8 }));
+}
+
+#[test]
+fn test_parse_incoming_save_and_load_messages() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/save".to_string(),
+        args: vec![rosc::OscType::String("set.json".to_string())],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Save { path: Some(p) } if p == "set.json"));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/load".to_string(),
+        args: vec![],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(msg, Message::Load { path: None }));
+}
+
+#[test]
+fn test_parse_incoming_import_midi_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/import_midi".to_string(),
+        args: vec![
+            rosc::OscType::String("groove.mid".to_string()),
+            rosc::OscType::Int(3),
+            rosc::OscType::String("track".to_string()),
+        ],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(
+        msg,
+        Message::ImportMidi {
+            path: Some(_),
+            ticks_per_step: Some(3),
+            by_track: true
+        }
+    ));
+
+    for ticks_per_step in &[0, -6] {
+        let packet = OscPacket::Message(OscMessage {
+            addr: "/brain/import_midi".to_string(),
+            args: vec![rosc::OscType::Int(*ticks_per_step)],
+        });
+        let msg = parse_incoming_osc_message(packet);
+        assert!(matches!(msg, Message::Unhandled));
+    }
+}
+
+#[test]
+fn test_parse_incoming_pattern_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/pattern".to_string(),
+        args: vec![rosc::OscType::Int(2), rosc::OscType::Int(16)],
+    });
+    let msg = parse_incoming_osc_message(packet);
+    assert!(matches!(
+        msg,
+        Message::QueuePattern {
+            bank: 1,
+            number: 15
+        }
+    ));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/pattern".to_string(),
+        args: vec![rosc::OscType::Int(0), rosc::OscType::Int(1)],
+    });
+    assert!(matches!(
+        parse_incoming_osc_message(packet),
+        Message::Unhandled
+    ));
+}
+
+#[test]
+fn test_parse_incoming_song_messages() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/song_append".to_string(),
+        args: vec![rosc::OscType::Int(1), rosc::OscType::Int(3)],
+    });
+    assert!(matches!(
+        parse_incoming_osc_message(packet),
+        Message::SongAppend {
+            bank: 0,
+            number: 2,
+            repeats: 1
+        }
+    ));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/song_loop".to_string(),
+        args: vec![rosc::OscType::Int(2), rosc::OscType::Int(4)],
+    });
+    assert!(matches!(
+        parse_incoming_osc_message(packet),
+        Message::SongLoop {
+            entries: Some((1, 3))
+        }
+    ));
+
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/song_jump".to_string(),
+        args: vec![rosc::OscType::Int(0)],
+    });
+    assert!(matches!(
+        parse_incoming_osc_message(packet),
+        Message::Unhandled
+    ));
+}
+
+#[test]
+fn test_parse_incoming_copy_steps_message() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/copy_steps".to_string(),
+        args: vec![
+            rosc::OscType::Int(2),
+            rosc::OscType::Int(1),
+            rosc::OscType::Int(4),
+            rosc::OscType::Int(5),
+        ],
+    });
+    assert!(matches!(
+        parse_incoming_osc_message(packet),
+        Message::CopySteps {
+            sequence: 1,
+            start: Step(1),
+            end: Step(4),
+            destination: Step(5)
+        }
+    ));
+}
+
+#[test]
+fn test_parse_incoming_copy_steps_message_clamps_steps() {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/brain/copy_steps".to_string(),
+        args: vec![
+            rosc::OscType::Int(1),
+            rosc::OscType::Int(1),
+            rosc::OscType::Int(i32::MAX),
+            rosc::OscType::Int(100),
+        ],
+    });
+    assert!(matches!(
+        parse_incoming_osc_message(packet),
+        Message::CopySteps {
+            sequence: 0,
+            start: Step(1),
+            end: Step(MAX_STEPS),
+            destination: Step(MAX_STEPS)
+        }
+    ));
+}
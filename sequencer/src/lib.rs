@@ -1,7 +1,23 @@
 pub mod atom;
+pub mod autosave;
+pub mod condition;
 pub mod config;
 pub mod context;
+pub mod direction;
+pub mod division;
 pub mod event;
+pub mod history;
 pub mod input;
+pub mod midi;
 pub mod output;
+pub mod pattern;
+pub mod project;
+pub mod random;
+pub mod scheduler;
 pub mod sequence;
+pub mod smf;
+pub mod song;
+pub mod swing;
+pub mod sync;
+pub mod tempo;
+pub mod time_signature;
@@ -1,53 +1,218 @@
 extern crate crossbeam;
 extern crate rosc;
 
-use crossbeam::crossbeam_channel::unbounded;
+use crossbeam::crossbeam_channel::{unbounded, Receiver, Sender};
+use std::fs;
 use std::net::UdpSocket;
+use std::path::Path;
 use std::thread;
-use std::time::Instant;
+use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
 use sequencer::atom;
+use sequencer::autosave;
+use sequencer::autosave::Autosave;
 use sequencer::config;
-use sequencer::context::Context;
+use sequencer::context::{Context, Transport};
 use sequencer::input;
-use sequencer::output;
-use sequencer::output::Output;
-use std::time::Duration;
+use sequencer::input::Message;
+use sequencer::midi;
+use sequencer::midi::SystemMessage;
+use sequencer::output::{DelayedOutput, Output};
+use sequencer::project;
+use sequencer::project::Project;
+use sequencer::scheduler;
+use sequencer::scheduler::{Scheduler, Timing};
+use sequencer::smf;
+use sequencer::sync::ClockFollower;
+
+// How long to wait for an external clock before handling other work
+const EXTERNAL_CLOCK_POLL: Duration = Duration::from_millis(10);
 
 fn main() {
     let o2m_output = Output::o2m();
+    let delayed_o2m_output = DelayedOutput::start(Output::o2m());
     let sampler_output = Output::sampler();
 
     config::parse();
+    let midi_output = config::midi_output();
+    let lookahead = config::lookahead();
+    let external_clock = config::external_clock();
+    let clock_output = config::clock_output();
+    let project_path = config::project();
+    let autosave_interval = config::autosave();
     atom::init();
 
     let (s, r) = unbounded();
+    let project_sender = s.clone();
 
     thread::spawn(move || {
-        let mut current_context = Context::default();
+        if let Err(e) = scheduler::set_realtime_priority() {
+            println!("Could not set realtime priority: {}", e);
+        }
 
-        loop {
-            o2m_output.send(output::clock_message());
+        let mut scheduler = Scheduler::new(Instant::now());
+        let mut clock_follower = ClockFollower::new();
+        let mut last_clock = Instant::now();
+        let mut current_context = Context {
+            seed: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64,
+            // An external clock starts playback with a MIDI Start
+            transport: match external_clock {
+                true => Transport::Stopped,
+                false => Transport::Playing,
+            },
+            ..Context::default()
+        };
 
-            let now = Instant::now();
+        let save_path = project_path
+            .clone()
+            .unwrap_or_else(|| project::DEFAULT_PATH.to_string());
+
+        if let Some(path) = &project_path {
+            match Project::load(path) {
+                Ok(project) => current_context = project.apply(&current_context),
+                Err(e) => println!("Could not load {}: {}", path, e),
+            }
+        }
 
-            let events = current_context.events();
-            for event in events {
+        let recovered_path = autosave::take_recovery(&save_path);
+        if let Some(path) = &recovered_path {
+            println!("Found an autosave in {}, press setup to restore it", path);
+            current_context = Context {
+                recovery_available: true,
+                ..current_context
+            };
+        }
+
+        let mut autosave = autosave_interval
+            .map(|interval| Autosave::start(autosave::recovery_path(&save_path), interval));
+
+        // Following an external clock, messages are still handled while
+        // waiting for it, but only a clock moves the tick on
+        let mut ticked = true;
+
+        loop {
+            // Everything the sequencer plays goes out at the tick's deadline
+            // plus the lookahead, which removes the jitter in when this thread
+            // wakes while keeping the sampler and MIDI in time with each
+            // other. Pads played live are sent straight away.
+            let due = match external_clock {
+                true => last_clock,
+                false => scheduler.deadline(),
+            };
+            let play_at = due + lookahead;
+
+            for event in &current_context.performance_events {
                 sampler_output.send(event.to_osc_message());
             }
-            current_context = current_context.clear_performance_events();
 
-            let messages = r.try_iter().collect();
-            let next_context = current_context.process_messages(messages).advance_tick();
+            // Note-offs due on a tick go out with the tick, but a live hit that
+            // retriggers a note has to end it first
+            let live_note_offs = match ticked {
+                true => current_context.retriggered_note_offs(),
+                false => current_context.performance_note_offs(),
+            };
+
+            if let Some(port) = &midi_output {
+                for note_off in &live_note_offs {
+                    o2m_output.send(note_off.to_osc_message(port));
+                }
+
+                for event in &current_context.performance_events {
+                    o2m_output.send(event.to_note_on_message(port));
+                }
+            }
+
+            current_context = match ticked {
+                true => {
+                    if !external_clock {
+                        delayed_o2m_output
+                            .send_at(SystemMessage::Clock.to_osc_message(&clock_output), play_at);
+                    }
+
+                    let events = current_context.sequenced_events();
+                    let sampler_messages = events.iter().map(|e| e.to_osc_message()).collect();
+                    sampler_output.send_bundle(sampler_messages, play_at);
+
+                    if let Some(port) = &midi_output {
+                        for note_off in current_context.note_offs() {
+                            if !live_note_offs.contains(&note_off) {
+                                delayed_o2m_output.send_at(note_off.to_osc_message(port), play_at);
+                            }
+                        }
+
+                        for event in events {
+                            delayed_o2m_output.send_at(event.to_note_on_message(port), play_at);
+                        }
+                    }
+
+                    current_context.schedule_note_offs()
+                }
+                false => current_context.schedule_performance_note_offs(),
+            }
+            .clear_performance_events();
+
+            // The tick advances before messages are processed so that starting
+            // from stopped plays tick 0. An external start is only played on
+            // the clock after it, which plays tick 0 rather than advancing.
+            let messages = match external_clock {
+                true => {
+                    let (messages, clock) = messages_until_clock(&r);
+                    if clock {
+                        last_clock = Instant::now();
+                        clock_follower.clock(last_clock);
+                    }
+                    ticked = clock;
+                    messages
+                }
+                false => r.try_iter().collect(),
+            };
+            let mut next_context = current_context.next(ticked, messages);
+
+            match external_clock {
+                true => {
+                    if let Some(bpm) = clock_follower.bpm() {
+                        next_context = Context {
+                            bpm,
+                            ..next_context
+                        };
+                    }
+                }
+                false => {
+                    for message in midi::transport_messages(&current_context, &next_context) {
+                        delayed_o2m_output.send_at(message.to_osc_message(&clock_output), play_at);
+                    }
+                }
+            }
+
+            if let Some(request) = &next_context.project_request {
+                handle_project_request(
+                    request,
+                    &save_path,
+                    recovered_path.as_deref(),
+                    &next_context,
+                    &project_sender,
+                );
+                next_context = next_context.clear_project_request();
+            }
+
+            if let Some(autosave) = &mut autosave {
+                autosave.update(&next_context, Instant::now());
+            }
 
             let o2m_messages = atom::update(&current_context, &next_context);
             for message in o2m_messages {
                 o2m_output.send(message);
             }
 
-            let elapsed_time = now.elapsed();
-            let sleep_time = tick_duration(current_context.bpm) - elapsed_time;
-            thread::sleep(sleep_time);
+            if !external_clock {
+                if let Timing::Resynchronised(lateness) = scheduler.wait(current_context.tempo()) {
+                    println!("Tick overran by {:?}, resynchronising", lateness);
+                }
+            }
 
             current_context = next_context;
         }
@@ -57,15 +222,116 @@ fn main() {
 
     loop {
         if let Some(msg) = input::process_incoming_message(&sock) {
-            s.send(msg).unwrap()
+            if external_clock || !input::is_external_clock(&msg) {
+                s.send(msg).unwrap()
+            }
         }
     }
 }
 
-fn tick_duration(bpm: f32) -> Duration {
-    let ms_per_beat = (60. / bpm) * 1000.;
-    let length_of_measure_in_beats = 4. / 96_f32;
-    let length_of_measure_in_ms = (length_of_measure_in_beats * ms_per_beat) as u64;
+// Waits for the external clock to tick, returning the messages that arrived
+// and whether the clock ticked. It returns early when any other message
+// arrives, or after a short wait, so that pads, the ATOM and project requests
+// are still handled when the clock stops.
+fn messages_until_clock(r: &Receiver<Message>) -> (Vec<Message>, bool) {
+    match r.recv_timeout(EXTERNAL_CLOCK_POLL) {
+        Ok(Message::Clock) => (vec![Message::Clock], true),
+        Ok(message) => (vec![message], false),
+        Err(_) => (vec![], false),
+    }
+}
+
+// Saving and loading happen on their own threads to keep file access off the
+// tick thread. A loaded project comes back as a message.
+fn handle_project_request(
+    request: &project::Request,
+    default_path: &str,
+    recovered_path: Option<&str>,
+    context: &Context,
+    sender: &Sender<Message>,
+) {
+    let path = match request {
+        project::Request::Save { path } => {
+            let path = path.clone().unwrap_or_else(|| default_path.to_string());
+            let project = Project::from_context(context);
+
+            thread::spawn(move || match project.save(&path) {
+                Ok(()) => println!("Saved {}", path),
+                Err(e) => println!("Could not save {}: {}", path, e),
+            });
+            return;
+        }
+        project::Request::Load { path } => path.clone().unwrap_or_else(|| default_path.to_string()),
+        project::Request::ExportMidi { path } => {
+            let path = path.clone().unwrap_or_else(|| {
+                Path::new(default_path)
+                    .with_extension("mid")
+                    .to_string_lossy()
+                    .to_string()
+            });
+            let context = context.clone();
+
+            thread::spawn(
+                move || match project::write_atomically(&path, &smf::export(&context)) {
+                    Ok(()) => println!("Exported {}", path),
+                    Err(e) => println!("Could not export {}: {}", path, e),
+                },
+            );
+            return;
+        }
+        project::Request::ImportMidi {
+            path,
+            division,
+            by_track,
+        } => {
+            let path = path.clone().unwrap_or_else(|| {
+                Path::new(default_path)
+                    .with_extension("mid")
+                    .to_string_lossy()
+                    .to_string()
+            });
+            let options = smf::ImportOptions {
+                division: *division,
+                time_signature: context.time_signature,
+                by_track: *by_track,
+            };
+            let sender = sender.clone();
+
+            thread::spawn(move || {
+                let import = match fs::read(&path) {
+                    Ok(bytes) => smf::import(&bytes, options).map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
 
-    Duration::from_millis(length_of_measure_in_ms)
+                match import {
+                    Ok(import) => {
+                        for note in import.dropped {
+                            println!("Could not import {}", note);
+                        }
+                        sender
+                            .send(Message::MidiImported {
+                                sequences: import.sequences,
+                            })
+                            .unwrap();
+                    }
+                    Err(e) => println!("Could not import {}: {}", path, e),
+                }
+            });
+            return;
+        }
+        project::Request::Restore => match recovered_path {
+            Some(path) => path.to_string(),
+            None => return,
+        },
+    };
+    let sender = sender.clone();
+
+    thread::spawn(move || match Project::load(&path) {
+        Ok(project) => sender
+            .send(Message::ProjectLoaded {
+                project: Box::new(project),
+            })
+            .unwrap(),
+        Err(e) => println!("Could not load {}: {}", path, e),
+    });
 }
@@ -0,0 +1,169 @@
+use crate::context::{Context, Transport};
+use rosc::OscMessage;
+
+// The MIDI system realtime and system common messages, sent through o2m
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SystemMessage {
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    Reset,
+    // In sixteenth notes from the start of the song
+    SongPosition(i32),
+    SongSelect(i32),
+    TuneRequest,
+}
+
+const MAX_SONG_POSITION: i32 = 0x3fff;
+
+impl SystemMessage {
+    pub fn to_osc_message(&self, port: &str) -> OscMessage {
+        let (message, args) = match self {
+            SystemMessage::Clock => ("clock", vec![]),
+            SystemMessage::Start => ("start", vec![]),
+            SystemMessage::Continue => ("continue", vec![]),
+            SystemMessage::Stop => ("stop", vec![]),
+            SystemMessage::ActiveSensing => ("active_sensing", vec![]),
+            // o2m has no named messages for these, so send the raw bytes
+            SystemMessage::Reset => ("raw", vec![0xff]),
+            SystemMessage::SongPosition(position) => {
+                let position = (*position).clamp(0, MAX_SONG_POSITION);
+                ("raw", vec![0xf2, position & 0x7f, position >> 7])
+            }
+            SystemMessage::SongSelect(song) => ("raw", vec![0xf3, song & 0x7f]),
+            SystemMessage::TuneRequest => ("raw", vec![0xf6]),
+        };
+
+        OscMessage {
+            addr: format!("/{}/{}", port, message),
+            args: args.into_iter().map(rosc::OscType::Int).collect(),
+        }
+    }
+}
+
+// The messages that keep external gear in phase with a change between two
+// ticks: Stop first, then the new position if it jumped, then Start or
+// Continue
+pub fn transport_messages(current: &Context, next: &Context) -> Vec<SystemMessage> {
+    let mut messages = vec![];
+
+    if current.transport == Transport::Playing && next.transport != Transport::Playing {
+        messages.push(SystemMessage::Stop);
+    }
+
+    // Counting in happens at negative ticks, before external gear starts
+    let jumped = next.tick != current.tick && next.tick != current.tick + 1;
+    if jumped && next.tick >= 0 && !(next.tick == 0 && current.transport == Transport::Stopped) {
+        messages.push(SystemMessage::SongPosition(next.tick / 6));
+    }
+
+    match (current.transport, next.transport) {
+        (Transport::Stopped, Transport::Playing) if next.tick >= 0 => {
+            messages.push(SystemMessage::Start)
+        }
+        (Transport::Playing, Transport::Playing) if current.tick == -1 && next.tick == 0 => {
+            messages.push(SystemMessage::Start)
+        }
+        (Transport::Paused, Transport::Playing) => messages.push(SystemMessage::Continue),
+        _ => (),
+    }
+
+    messages
+}
+
+#[test]
+fn test_to_osc_message() {
+    let message = SystemMessage::Clock.to_osc_message("*");
+    assert_eq!("/*/clock", message.addr);
+    assert!(message.args.is_empty());
+
+    let message = SystemMessage::SongPosition(200).to_osc_message("volca");
+    assert_eq!("/volca/raw", message.addr);
+    assert_eq!(
+        vec![
+            rosc::OscType::Int(0xf2),
+            rosc::OscType::Int(72),
+            rosc::OscType::Int(1)
+        ],
+        message.args
+    );
+}
+
+#[test]
+fn test_transport_messages() {
+    let playing = Context::default();
+    let paused = Context {
+        transport: Transport::Paused,
+        ..playing.clone()
+    };
+    let stopped = Context {
+        transport: Transport::Stopped,
+        ..playing.clone()
+    };
+
+    assert_eq!(
+        vec![SystemMessage::Start],
+        transport_messages(&stopped, &playing)
+    );
+    assert_eq!(
+        vec![SystemMessage::Continue],
+        transport_messages(&paused, &playing)
+    );
+    assert_eq!(
+        vec![SystemMessage::Stop],
+        transport_messages(&playing, &paused)
+    );
+    assert!(transport_messages(&playing, &playing).is_empty());
+}
+
+#[test]
+fn test_transport_messages_when_jumping_position() {
+    let playing = Context {
+        tick: 96,
+        ..Context::default()
+    };
+    let stopped = Context {
+        transport: Transport::Stopped,
+        tick: 0,
+        ..playing.clone()
+    };
+
+    assert_eq!(
+        vec![SystemMessage::Stop, SystemMessage::SongPosition(0)],
+        transport_messages(&playing, &stopped)
+    );
+
+    let moved = Context {
+        tick: 48,
+        ..stopped.clone()
+    };
+    assert_eq!(
+        vec![SystemMessage::SongPosition(8)],
+        transport_messages(&stopped, &moved)
+    );
+}
+
+#[test]
+fn test_transport_messages_after_count_in() {
+    let stopped = Context {
+        transport: Transport::Stopped,
+        ..Context::default()
+    };
+    let counting_in = Context {
+        tick: -96,
+        ..Context::default()
+    };
+
+    assert!(transport_messages(&stopped, &counting_in).is_empty());
+
+    let last_click = Context {
+        tick: -1,
+        ..Context::default()
+    };
+    assert_eq!(
+        vec![SystemMessage::Start],
+        transport_messages(&last_click, &last_click.advance_tick())
+    );
+}
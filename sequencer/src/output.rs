@@ -1,8 +1,14 @@
+use crossbeam::crossbeam_channel::{unbounded, Sender};
 use rosc::encoder;
-use rosc::{OscMessage, OscPacket};
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
 use std::net::SocketAddrV4;
 use std::net::UdpSocket;
 use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
 
 pub struct Output {
     socket: UdpSocket,
@@ -25,19 +31,107 @@ impl Output {
     }
 
     pub fn send(&self, message: OscMessage) {
-        let packet = encoder::encode(&OscPacket::Message(message)).unwrap();
+        self.send_packet(OscPacket::Message(message));
+    }
+
+    // Sends the messages in a bundle, so the receiver can schedule them for
+    // the given time rather than acting on them as they arrive
+    pub fn send_bundle(&self, messages: Vec<OscMessage>, time: Instant) {
+        if messages.is_empty() {
+            return;
+        }
+
+        self.send_packet(bundle(messages, system_time(time, Instant::now())));
+    }
+
+    fn send_packet(&self, packet: OscPacket) {
+        let packet = encoder::encode(&packet).unwrap();
         self.socket.send_to(&packet, self.to_addr).unwrap();
     }
 }
 
+// Sends messages at a later time from its own thread, for receivers like o2m
+// that act on messages as they arrive rather than scheduling bundles.
+// Messages must be sent in time order.
+pub struct DelayedOutput {
+    sender: Sender<(Instant, OscMessage)>,
+}
+
+impl DelayedOutput {
+    pub fn start(output: Output) -> DelayedOutput {
+        let (sender, receiver) = unbounded::<(Instant, OscMessage)>();
+
+        thread::spawn(move || {
+            for (time, message) in receiver.iter() {
+                let now = Instant::now();
+                if time > now {
+                    thread::sleep(time - now);
+                }
+                output.send(message);
+            }
+        });
+
+        DelayedOutput { sender }
+    }
+
+    pub fn send_at(&self, message: OscMessage, time: Instant) {
+        self.sender.send((time, message)).unwrap();
+    }
+}
+
 pub fn send_osc_message_to_o2m(message: OscMessage) {
     let output = Output::o2m();
     output.send(message);
 }
 
-pub fn clock_message() -> OscMessage {
-    OscMessage {
-        addr: "/*/clock".to_string(),
-        args: vec![],
+fn bundle(messages: Vec<OscMessage>, time: SystemTime) -> OscPacket {
+    OscPacket::Bundle(OscBundle {
+        timetag: timetag(time),
+        content: messages.into_iter().map(OscPacket::Message).collect(),
+    })
+}
+
+fn system_time(instant: Instant, now: Instant) -> SystemTime {
+    match instant > now {
+        true => SystemTime::now() + (instant - now),
+        false => SystemTime::now() - (now - instant),
+    }
+}
+
+// An NTP timestamp: seconds since 1900 and a 32 bit fraction of a second
+fn timetag(time: SystemTime) -> OscType {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0));
+    let seconds = since_epoch.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (u64::from(since_epoch.subsec_nanos()) << 32) / 1_000_000_000;
+
+    OscType::Time(seconds as u32, fraction as u32)
+}
+
+#[test]
+fn test_timetag() {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1500);
+
+    assert_eq!(
+        OscType::Time(NTP_UNIX_OFFSET as u32 + 1, 1 << 31),
+        timetag(time)
+    );
+}
+
+#[test]
+fn test_bundle() {
+    let message = OscMessage {
+        addr: "/sampler".to_string(),
+        args: vec![OscType::Int(36)],
+    };
+    let packet = bundle(vec![message], SystemTime::UNIX_EPOCH);
+
+    match packet {
+        OscPacket::Bundle(bundle) => {
+            assert_eq!(OscType::Time(NTP_UNIX_OFFSET as u32, 0), bundle.timetag);
+            assert_eq!(1, bundle.content.len());
+        }
+        _ => panic!("expected a bundle"),
     }
 }
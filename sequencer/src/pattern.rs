@@ -0,0 +1,89 @@
+use crate::sequence::Sequence;
+use serde::{Deserialize, Serialize};
+
+pub const BANKS: usize = 16;
+pub const PATTERNS_PER_BANK: usize = 16;
+pub const NUMBER_OF_PATTERNS: usize = BANKS * PATTERNS_PER_BANK;
+
+// One sequence for each pad
+pub const MAX_SEQUENCES: usize = 16;
+
+// A full set of sequences, and optionally the tempo to change to when the
+// pattern starts playing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pattern {
+    pub bpm: Option<f32>,
+    pub sequences: Vec<Sequence>,
+}
+
+// Where a queued pattern takes over from the one that is playing
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Switch {
+    #[default]
+    Bar,
+    Pattern,
+}
+
+impl Default for Pattern {
+    fn default() -> Pattern {
+        let mut sequences = vec![];
+        for n in 0..MAX_SEQUENCES as i32 {
+            sequences.push(Sequence::with_default_note_number(n + 36).with_midi_channel(n + 1));
+        }
+
+        Pattern {
+            bpm: None,
+            sequences,
+        }
+    }
+}
+
+impl Pattern {
+    // The number of ticks before every sequence has played through at least
+    // once
+    pub fn length_in_ticks(sequences: &[Sequence]) -> i32 {
+        sequences
+            .iter()
+            .map(|s| s.loop_length_in_ticks())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// Patterns are numbered from 0, running through each bank in turn
+pub fn index(bank: usize, number: usize) -> Option<usize> {
+    match bank < BANKS && number < PATTERNS_PER_BANK {
+        true => Some(bank * PATTERNS_PER_BANK + number),
+        false => None,
+    }
+}
+
+pub fn bank(index: usize) -> usize {
+    index / PATTERNS_PER_BANK
+}
+
+pub fn number_in_bank(index: usize) -> usize {
+    index % PATTERNS_PER_BANK
+}
+
+#[test]
+fn test_index() {
+    assert_eq!(Some(0), index(0, 0));
+    assert_eq!(Some(17), index(1, 1));
+    assert_eq!(Some(255), index(15, 15));
+    assert_eq!(None, index(16, 0));
+    assert_eq!(None, index(0, 16));
+
+    assert_eq!(1, bank(17));
+    assert_eq!(1, number_in_bank(17));
+}
+
+#[test]
+fn test_length_in_ticks() {
+    let sequences = vec![Sequence::empty(), Sequence::empty().set_length(32)];
+
+    assert_eq!(32 * 6, Pattern::length_in_ticks(&sequences));
+    assert_eq!(0, Pattern::length_in_ticks(&[]));
+}
@@ -0,0 +1,403 @@
+use crate::context::Context;
+use crate::division::Division;
+use crate::history::History;
+use crate::pattern;
+use crate::pattern::Pattern;
+use crate::sequence::Sequence;
+use crate::song::Song;
+use crate::swing::Swing;
+use crate::time_signature::TimeSignature;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+
+// Bump this when a change to the file format can't be handled by giving new
+// fields a default
+pub const VERSION: u32 = 2;
+
+pub const DEFAULT_PATH: &str = "project.json";
+
+// The parts of a Context that make up a piece of music, as opposed to the
+// state of the controller or the transport
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub version: u32,
+    pub bpm: f32,
+    pub swing: Swing,
+    pub time_signature: TimeSignature,
+    pub pattern_switch: pattern::Switch,
+    pub pattern: usize,
+    pub patterns: BTreeMap<usize, Pattern>,
+    pub song: Song,
+    pub song_mode: bool,
+    // Version 1 projects had a single set of sequences, which are read into
+    // the first pattern
+    #[serde(skip_serializing)]
+    pub sequences: Vec<Sequence>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Save {
+        path: Option<String>,
+    },
+    Load {
+        path: Option<String>,
+    },
+    Restore,
+    ExportMidi {
+        path: Option<String>,
+    },
+    ImportMidi {
+        path: Option<String>,
+        division: Division,
+        by_track: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::UnsupportedVersion(v) => write!(
+                f,
+                "project version {} is newer than this version of brain supports ({})",
+                v, VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl Default for Project {
+    fn default() -> Project {
+        Project::from_context(&Context::default())
+    }
+}
+
+impl Project {
+    pub fn from_context(context: &Context) -> Project {
+        Project {
+            version: VERSION,
+            bpm: context.bpm,
+            swing: context.swing,
+            time_signature: context.time_signature,
+            pattern_switch: context.pattern_switch,
+            pattern: context.pattern,
+            patterns: context.patterns(),
+            song: context.song.clone(),
+            song_mode: context.song_mode,
+            sequences: vec![],
+        }
+    }
+
+    pub fn apply(&self, context: &Context) -> Context {
+        let mut patterns = self.patterns.clone();
+        let current = patterns.remove(&self.pattern).unwrap_or_default();
+
+        Context {
+            bpm: current.bpm.unwrap_or(self.bpm),
+            swing: self.swing,
+            time_signature: self.time_signature,
+            pattern_switch: self.pattern_switch,
+            pattern: self.pattern,
+            pattern_bpm: current.bpm,
+            queued_pattern: None,
+            pattern_start: context.tick,
+            patterns: Arc::new(patterns),
+            song: self.song.clone(),
+            song_mode: self.song_mode,
+            song_entry: 0,
+            queued_song_entry: None,
+            history: History::default(),
+            selected_sequence: context.selected_sequence.min(current.sequences.len() - 1),
+            sequences: current.sequences,
+            held_step: None,
+            tempo_ramp: None,
+            ..context.clone()
+        }
+    }
+
+    // Steps without triggers may be missing from a hand edited file, and the
+    // sequences rely on every step up to their length being present. Each
+    // pattern needs a sequence for every pad.
+    fn normalise(&self) -> Project {
+        let mut patterns = self.patterns.clone();
+        if !self.sequences.is_empty() {
+            patterns.insert(
+                0,
+                Pattern {
+                    bpm: None,
+                    sequences: self.sequences.clone(),
+                },
+            );
+        }
+
+        let patterns = patterns
+            .into_iter()
+            .filter(|(n, _)| *n < pattern::NUMBER_OF_PATTERNS)
+            .map(|(n, p)| {
+                let sequences = Pattern::default()
+                    .sequences
+                    .iter()
+                    .enumerate()
+                    .map(|(n, empty)| match p.sequences.get(n) {
+                        Some(s) => s.normalise(),
+                        None => empty.clone(),
+                    })
+                    .collect();
+                (n, Pattern { sequences, ..p })
+            })
+            .collect();
+
+        // Entries for patterns that don't exist are dropped, and the loop
+        // is kept only if it still fits the song
+        let song = Song {
+            entries: self
+                .song
+                .entries
+                .iter()
+                .filter(|e| e.pattern < pattern::NUMBER_OF_PATTERNS)
+                .copied()
+                .collect(),
+            repeat: None,
+        };
+
+        Project {
+            version: VERSION,
+            pattern: self.pattern.min(pattern::NUMBER_OF_PATTERNS - 1),
+            song: song.set_loop(self.song.repeat),
+            patterns,
+            sequences: vec![],
+            ..self.clone()
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Project, Error> {
+        let project: Project = serde_json::from_str(json)?;
+
+        match project.version > VERSION {
+            true => Err(Error::UnsupportedVersion(project.version)),
+            false => Ok(project.normalise()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        Ok(write_atomically(path, self.to_json()?.as_bytes())?)
+    }
+
+    pub fn load(path: &str) -> Result<Project, Error> {
+        Project::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+// Writes to a temporary file that is synced to disk and then renamed into
+// place, so a crash or power cut while saving can't leave a half written file
+pub fn write_atomically(path: &str, contents: &[u8]) -> io::Result<()> {
+    let temporary_path = format!("{}.tmp", path);
+    let mut file = fs::File::create(&temporary_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, path)
+}
+
+#[cfg(test)]
+use crate::input::Message;
+#[cfg(test)]
+use crate::sequence::MAX_STEPS;
+
+#[test]
+fn test_round_trip() {
+    use crate::sequence::Step;
+
+    let context = Context {
+        bpm: 97.5,
+        ..Context::default()
+    }
+    .toggle_step_for_selected_sequence(3);
+
+    let json = Project::from_context(&context).to_json().unwrap();
+    let loaded = Project::from_json(&json)
+        .unwrap()
+        .apply(&Context::default());
+
+    assert_eq!(97.5, loaded.bpm);
+    assert!(loaded.sequences[0].active_steps().contains(&Step(3)));
+    assert_eq!(16, loaded.sequences.len());
+}
+
+#[test]
+fn test_load_project_with_missing_fields() {
+    let json = r#"{
+        "version": 2,
+        "bpm": 100.0,
+        "patterns": { "0": {
+        "sequences": [{
+            "number_of_steps": 4,
+            "triggers": { "1": [{ "note_number": 36, "velocity": 100, "length": 6 }] }
+        }] }
+        }
+    }"#;
+
+    let project = Project::from_json(json).unwrap();
+
+    assert_eq!(100.0, project.bpm);
+    assert_eq!(TimeSignature::default(), project.time_signature);
+    assert_eq!(4, project.patterns[&0].sequences[0].number_of_steps());
+    assert_eq!(1, project.patterns[&0].sequences[0].active_steps().len());
+
+    let context = project.apply(&Context::default());
+    assert_eq!(
+        1,
+        context.sequences[0]
+            .events_for_tick(0, &context.playback())
+            .len()
+    );
+    assert!(context.sequences[0]
+        .events_for_tick(6, &context.playback())
+        .is_empty());
+}
+
+#[test]
+fn test_newer_version_is_rejected() {
+    let json = format!(r#"{{ "version": {} }}"#, VERSION + 1);
+
+    assert!(matches!(
+        Project::from_json(&json),
+        Err(Error::UnsupportedVersion(_))
+    ));
+}
+
+#[test]
+fn test_load_version_1_project() {
+    let json = r#"{
+        "version": 1,
+        "bpm": 100.0,
+        "sequences": [{ "triggers": { "2": [{ "note_number": 36, "velocity": 100, "length": 6 }] } }]
+    }"#;
+
+    let context = Project::from_json(json).unwrap().apply(&Context::default());
+
+    assert_eq!(0, context.pattern);
+    assert_eq!(1, context.sequences[0].active_steps().len());
+}
+
+#[test]
+fn test_round_trip_patterns() {
+    let context = Context::default()
+        .process_messages(vec![
+            Message::Stop,
+            Message::QueuePattern { bank: 1, number: 0 },
+            Message::PatternTempo { bpm: Some(90.0) },
+        ])
+        .toggle_step_for_selected_sequence(5);
+
+    let json = Project::from_context(&context).to_json().unwrap();
+    let loaded = Project::from_json(&json)
+        .unwrap()
+        .apply(&Context::default());
+
+    assert_eq!(16, loaded.pattern);
+    assert_eq!(90.0, loaded.bpm);
+    assert_eq!(1, loaded.sequences[0].active_steps().len());
+    assert!(loaded.patterns[&0].sequences[0].active_steps().is_empty());
+}
+
+#[test]
+fn test_round_trip_song() {
+    let context = Context::default().process_messages(vec![
+        Message::SongAppend {
+            bank: 0,
+            number: 1,
+            repeats: 2,
+        },
+        Message::SongAppend {
+            bank: 0,
+            number: 0,
+            repeats: 1,
+        },
+        Message::SongLoop {
+            entries: Some((0, 1)),
+        },
+        Message::SongMode { on: true },
+    ]);
+
+    let json = Project::from_context(&context).to_json().unwrap();
+    let loaded = Project::from_json(&json)
+        .unwrap()
+        .apply(&Context::default());
+
+    assert_eq!(context.song, loaded.song);
+    assert!(loaded.song_mode);
+}
+
+#[test]
+fn test_load_project_with_fewer_sequences() {
+    let json = r#"{
+        "version": 2,
+        "patterns": { "0": { "sequences": [{ "number_of_steps": 8 }] } }
+    }"#;
+
+    let context = Project::from_json(json).unwrap().apply(&Context::default());
+
+    assert_eq!(16, context.sequences.len());
+    assert_eq!(8, context.sequences[0].number_of_steps());
+    assert_eq!(3, context.sequences[2].midi_channel());
+
+    let context = context.process_messages(vec![Message::NoteOn {
+        note_number: 51,
+        velocity: 127,
+    }]);
+    assert_eq!(51, context.events()[0].note_number);
+}
+
+#[test]
+fn test_load_project_with_invalid_length_and_division() {
+    let json = r#"{
+        "version": 2,
+        "patterns": { "0": { "sequences": [
+            { "number_of_steps": 0 },
+            { "number_of_steps": 1000, "division": 0 }
+        ] } }
+    }"#;
+
+    let context = Project::from_json(json).unwrap().apply(&Context::default());
+
+    assert_eq!(1, context.sequences[0].number_of_steps());
+    assert_eq!(MAX_STEPS, context.sequences[1].number_of_steps());
+    assert_eq!(1, context.sequences[1].division().ticks_per_step());
+
+    let mut context = context;
+    for _ in 0..100 {
+        context = context.advance_tick();
+        context.events();
+    }
+}
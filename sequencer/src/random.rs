@@ -0,0 +1,35 @@
+// Rather than keeping the state of a random number generator in Context,
+// random values are derived by hashing a seed together with values that
+// identify what the random value is for. This keeps Context immutable and
+// means the same tick always produces the same result for a given seed.
+pub fn random(seed: u64, values: &[i32]) -> u64 {
+    values
+        .iter()
+        .fold(mix(seed), |hash, value| mix(hash ^ (*value as u32 as u64)))
+}
+
+pub fn percentage(seed: u64, values: &[i32]) -> i32 {
+    (random(seed, values) % 100) as i32
+}
+
+// splitmix64, see http://xoshiro.di.unimi.it/splitmix64.c
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn test_random_is_deterministic() {
+    assert_eq!(random(1, &[2, 3]), random(1, &[2, 3]));
+    assert_ne!(random(1, &[2, 3]), random(2, &[2, 3]));
+    assert_ne!(random(1, &[2, 3]), random(1, &[3, 2]));
+}
+
+#[test]
+fn test_percentage_distribution() {
+    let below_half = (0..1000).filter(|n| percentage(0, &[*n]) < 50).count();
+
+    assert!(below_half > 450 && below_half < 550);
+}
@@ -0,0 +1,139 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// If the loop falls further behind than this (e.g. the Pi was busy or the
+// process was suspended) the ticks it missed are dropped rather than sent
+// in a burst
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+#[derive(Debug, PartialEq)]
+pub enum Timing {
+    OnTime,
+    Late(Duration),
+    Resynchronised(Duration),
+}
+
+// Each deadline is the start instant plus the sum of every tick length so
+// far, so rounding and the time spent processing a tick never accumulate
+pub struct Scheduler {
+    start: Instant,
+    elapsed: Duration,
+}
+
+impl Scheduler {
+    pub fn new(start: Instant) -> Scheduler {
+        Scheduler {
+            start,
+            elapsed: Duration::from_secs(0),
+        }
+    }
+
+    // The time the current tick is due
+    pub fn deadline(&self) -> Instant {
+        self.start + self.elapsed
+    }
+
+    pub fn next_deadline(&mut self, bpm: f32) -> Instant {
+        self.elapsed += tick_duration(bpm);
+        self.start + self.elapsed
+    }
+
+    pub fn wait(&mut self, bpm: f32) -> Timing {
+        let deadline = self.next_deadline(bpm);
+        let timing = self.timing(deadline, Instant::now());
+
+        if let Timing::OnTime = timing {
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            }
+        }
+
+        timing
+    }
+
+    fn timing(&mut self, deadline: Instant, now: Instant) -> Timing {
+        if now <= deadline {
+            Timing::OnTime
+        } else if now - deadline <= MAX_CATCH_UP {
+            Timing::Late(now - deadline)
+        } else {
+            self.start = now;
+            self.elapsed = Duration::from_secs(0);
+            Timing::Resynchronised(now - deadline)
+        }
+    }
+}
+
+pub fn tick_duration(bpm: f32) -> Duration {
+    let seconds_per_beat = 60. / bpm as f64;
+    let ticks_per_beat = 24.;
+
+    Duration::from_nanos((seconds_per_beat / ticks_per_beat * 1e9) as u64)
+}
+
+// Asks for SCHED_FIFO scheduling for the calling thread, which usually needs
+// root or an rtprio limit set in /etc/security/limits.conf
+#[cfg(target_os = "linux")]
+pub fn set_realtime_priority() -> Result<(), std::io::Error> {
+    let param = libc::sched_param { sched_priority: 80 };
+
+    match unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) } {
+        0 => Ok(()),
+        error => Err(std::io::Error::from_raw_os_error(error)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_realtime_priority() -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "realtime priority is only supported on Linux",
+    ))
+}
+
+#[test]
+fn test_tick_duration() {
+    assert_eq!(Duration::from_nanos(20_833_333), tick_duration(120.0));
+    assert_eq!(Duration::from_nanos(17_361_111), tick_duration(144.0));
+}
+
+#[test]
+fn test_deadlines_do_not_drift() {
+    let start = Instant::now();
+    let mut scheduler = Scheduler::new(start);
+
+    let mut deadline = start;
+    for _ in 0..96 {
+        deadline = scheduler.next_deadline(120.0);
+    }
+
+    assert_eq!(Duration::from_nanos(20_833_333 * 96), deadline - start);
+}
+
+#[test]
+fn test_timing() {
+    let start = Instant::now();
+    let mut scheduler = Scheduler::new(start);
+    let deadline = scheduler.next_deadline(120.0);
+
+    assert_eq!(Timing::OnTime, scheduler.timing(deadline, start));
+    assert_eq!(
+        Timing::Late(Duration::from_millis(5)),
+        scheduler.timing(deadline, deadline + Duration::from_millis(5))
+    );
+}
+
+#[test]
+fn test_resynchronise_when_far_behind() {
+    let start = Instant::now();
+    let mut scheduler = Scheduler::new(start);
+    let deadline = scheduler.next_deadline(120.0);
+    let now = deadline + Duration::from_secs(1);
+
+    assert_eq!(
+        Timing::Resynchronised(Duration::from_secs(1)),
+        scheduler.timing(deadline, now)
+    );
+    assert_eq!(now + tick_duration(120.0), scheduler.next_deadline(120.0));
+}
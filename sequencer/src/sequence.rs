@@ -1,22 +1,143 @@
-use crate::event::Event;
+use crate::condition::Condition;
+use crate::direction::Direction;
+use crate::division::Division;
+use crate::event::{Event, DEFAULT_LENGTH, DEFAULT_VELOCITY};
+use crate::random;
+use crate::swing;
+use crate::swing::Swing;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+const RATCHETS: [i32; 5] = [1, 2, 3, 4, 6];
+pub const MAX_STEPS: i32 = 64;
+pub const STEPS_PER_PAGE: i32 = 16;
+// The longest gate, in ticks
+const MAX_GATE: i32 = 96;
+
+// Fields added after the first version of the project file have defaults so
+// that older projects still load
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Trigger {
     pub note_number: i32,
+    pub velocity: i32,
+    pub length: i32,
+    #[serde(default)]
     offset: i32,
+    #[serde(default)]
+    condition: Condition,
+    #[serde(default = "default_ratchets")]
+    ratchets: i32,
+    #[serde(default)]
+    ramp: Ramp,
+}
+
+fn default_ratchets() -> i32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Ramp {
+    Down,
+    #[default]
+    Flat,
+    Up,
+}
+
+impl Trigger {
+    // A step can't be split into more hits than it has ticks, so at fine
+    // divisions the ratchets are limited to one hit per tick
+    fn ratchet_hits(&self, tick: i32, ticks_per_step: i32) -> Vec<(i32, Trigger)> {
+        let ratchets = self.ratchets.min(ticks_per_step).max(1);
+        let length = match ratchets {
+            1 => self.length,
+            _ => self.length.min((ticks_per_step / ratchets).max(1)),
+        };
+
+        (0..ratchets)
+            .map(|n| {
+                let velocity = match self.ramp {
+                    Ramp::Down => self.velocity * (ratchets - n) / ratchets,
+                    Ramp::Flat => self.velocity,
+                    Ramp::Up => self.velocity * (n + 1) / ratchets,
+                };
+                let hit = Trigger {
+                    velocity,
+                    length,
+                    ..self.clone()
+                };
+
+                (tick + n * ticks_per_step / ratchets, hit)
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Step(pub i32);
 
-#[derive(Debug, Clone)]
+impl Step {
+    pub fn on_page(page: i32, pad: i32) -> Step {
+        Step(page * STEPS_PER_PAGE + pad)
+    }
+
+    pub fn page(&self) -> i32 {
+        (self.0 - 1).div_euclid(STEPS_PER_PAGE)
+    }
+
+    pub fn pad(&self) -> i32 {
+        (self.0 - 1).rem_euclid(STEPS_PER_PAGE) + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Playback {
+    pub swing: Swing,
+    pub fill: bool,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Sequence {
+    #[serde(serialize_with = "serialize_triggers")]
     triggers: HashMap<Step, HashSet<Trigger>>,
     number_of_steps: i32,
     mute: bool,
     default_note_number: i32,
+    gate: i32,
+    midi_channel: i32,
+    swing_amount: Option<i32>,
+    division: Division,
+    direction: Direction,
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence::empty()
+    }
+}
+
+// Writes triggers in step and note order so that saved projects are stable
+// and easy to compare
+fn serialize_triggers<S>(
+    triggers: &HashMap<Step, HashSet<Trigger>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let ordered: BTreeMap<i32, Vec<&Trigger>> = triggers
+        .iter()
+        .map(|(step, triggers)| {
+            let mut triggers: Vec<&Trigger> = triggers.iter().collect();
+            triggers.sort_by_key(|t| (t.note_number, t.offset));
+            (step.0, triggers)
+        })
+        .collect();
+
+    ordered.serialize(serializer)
 }
 
 impl Sequence {
@@ -32,6 +153,11 @@ impl Sequence {
             number_of_steps: 16,
             mute: false,
             default_note_number: 1,
+            gate: DEFAULT_LENGTH,
+            midi_channel: 1,
+            swing_amount: None,
+            division: Division::default(),
+            direction: Direction::default(),
         }
     }
 
@@ -42,31 +168,96 @@ impl Sequence {
         }
     }
 
-    pub fn triggers_for_tick(&self, tick: i32) -> Vec<Trigger> {
-        let ticks_per_step = 6;
-        let sequence_length_in_ticks = self.number_of_steps * ticks_per_step;
-        let offset_into_sequence = tick % sequence_length_in_ticks;
-        let nearest_step = Step((offset_into_sequence / ticks_per_step) + 1);
-        let offset_into_step = offset_into_sequence % ticks_per_step;
+    pub fn with_midi_channel(&self, midi_channel: i32) -> Sequence {
+        Sequence {
+            midi_channel,
+            ..self.clone()
+        }
+    }
+
+    pub fn triggers_for_tick(&self, tick: i32, playback: &Playback) -> Vec<Trigger> {
+        let ticks_per_step = self.division.ticks_per_step();
+        let max_offset = self.max_offset();
+        let swing = self.swing(playback);
+        let earliest_position =
+            (tick - max_offset - swing.max_delay() - ticks_per_step + 1).div_euclid(ticks_per_step);
+        let latest_position = (tick + max_offset).div_euclid(ticks_per_step);
 
         match self.mute {
-            false => self
-                .triggers
-                .get(&nearest_step)
-                .unwrap()
-                .clone()
-                .into_iter()
-                .filter(|t| t.offset == offset_into_step)
+            false => (earliest_position..=latest_position)
+                .flat_map(|p| {
+                    let step_start = p * ticks_per_step;
+                    let step_tick = step_start + swing.delay(step_start);
+                    self.triggers
+                        .get(&self.step_at_position(p, playback))
+                        .unwrap()
+                        .iter()
+                        .flat_map(move |t| t.ratchet_hits(step_tick + t.offset, ticks_per_step))
+                        .filter(move |(hit_tick, t)| {
+                            *hit_tick == tick && self.condition_met(t, p, playback)
+                        })
+                        .map(|(_, t)| t)
+                })
                 .collect(),
             true => vec![],
         }
     }
 
-    pub fn events_for_tick(&self, tick: i32) -> Vec<Event> {
-        self.triggers_for_tick(tick)
+    fn condition_met(&self, trigger: &Trigger, position: i32, playback: &Playback) -> bool {
+        let loop_number = position.div_euclid(self.direction.loop_length(self.number_of_steps));
+        let random = random::percentage(
+            playback.seed,
+            &[self.midi_channel, position, trigger.note_number],
+        );
+
+        trigger
+            .condition
+            .is_met(loop_number, playback.fill, random, || {
+                self.previous_condition_met(position, playback)
+            })
+    }
+
+    // Looks back up to one loop of the sequence for the most recent trigger
+    // with a condition other than Previous/NotPrevious
+    fn previous_condition_met(&self, position: i32, playback: &Playback) -> bool {
+        for p in ((position - self.number_of_steps)..position).rev() {
+            let previous_trigger = self
+                .triggers
+                .get(&self.step_at_position(p, playback))
+                .unwrap()
+                .iter()
+                .filter(|t| t.condition != Condition::Always && !t.condition.is_previous())
+                .min_by_key(|t| t.note_number);
+
+            if let Some(t) = previous_trigger {
+                return self.condition_met(t, p, playback);
+            }
+        }
+
+        false
+    }
+
+    fn swing(&self, playback: &Playback) -> Swing {
+        match self.swing_amount {
+            Some(amount) => playback.swing.with_amount(amount),
+            None => playback.swing,
+        }
+    }
+
+    fn step_at_position(&self, position: i32, playback: &Playback) -> Step {
+        let seed = random::random(playback.seed, &[self.midi_channel]);
+
+        Step(self.direction.index(position, self.number_of_steps, seed) + 1)
+    }
+
+    pub fn events_for_tick(&self, tick: i32, playback: &Playback) -> Vec<Event> {
+        self.triggers_for_tick(tick, playback)
             .iter()
             .map(|t| Event {
                 note_number: t.note_number,
+                velocity: t.velocity,
+                length: t.length,
+                channel: self.midi_channel,
             })
             .collect()
     }
@@ -74,19 +265,69 @@ impl Sequence {
     pub fn default_event(&self) -> Event {
         Event {
             note_number: self.default_note_number,
+            velocity: DEFAULT_VELOCITY,
+            length: self.gate,
+            channel: self.midi_channel,
         }
     }
 
     fn trigger_note_number_at_step(&self, note_number: i32, step: Step) -> Sequence {
+        self.trigger_note_number_at_step_with_velocity(note_number, DEFAULT_VELOCITY, step)
+    }
+
+    fn trigger_note_number_at_step_with_velocity(
+        &self,
+        note_number: i32,
+        velocity: i32,
+        step: Step,
+    ) -> Sequence {
         let new_trigger = Trigger {
             note_number,
+            velocity,
+            length: self.gate,
             offset: 0,
+            condition: Condition::Always,
+            ratchets: 1,
+            ramp: Ramp::Flat,
         };
+
+        self.insert_trigger(step, new_trigger)
+    }
+
+    // Adds a note played at an offset from the start of the step, e.g. one
+    // imported from a MIDI file
+    pub fn add_note(
+        &self,
+        step: Step,
+        note_number: i32,
+        velocity: i32,
+        length: i32,
+        offset: i32,
+    ) -> Sequence {
+        let max_offset = self.max_offset();
+
+        self.insert_trigger(
+            step,
+            Trigger {
+                note_number,
+                velocity,
+                length,
+                offset: offset.clamp(-max_offset, max_offset),
+                condition: Condition::Always,
+                ratchets: 1,
+                ramp: Ramp::Flat,
+            },
+        )
+    }
+
+    fn insert_trigger(&self, step: Step, new_trigger: Trigger) -> Sequence {
+        let note_number = new_trigger.note_number;
         let mut triggers = self.triggers.clone();
 
         match self.triggers.get(&step) {
             Some(t) => {
                 let mut step_triggers = t.clone();
+                step_triggers.retain(|t| t.note_number != note_number);
                 step_triggers.insert(new_trigger);
                 triggers.insert(step, step_triggers);
             }
@@ -103,23 +344,24 @@ impl Sequence {
         }
     }
 
-    fn toggle_note_number_at_step(&self, note_number: i32, step: Step) -> Sequence {
+    fn toggle_note_number_at_step(&self, note_number: i32, velocity: i32, step: Step) -> Sequence {
         match self.has_note_number_at_step(note_number, step) {
             true => self.remove_note_number_at_step(note_number, step),
-            false => self.trigger_note_number_at_step(note_number, step),
+            false => self.trigger_note_number_at_step_with_velocity(note_number, velocity, step),
         }
     }
 
     pub fn toggle_step(&self, step: Step) -> Sequence {
-        self.toggle_note_number_at_step(self.default_note_number, step)
+        self.toggle_step_with_velocity(step, DEFAULT_VELOCITY)
+    }
+
+    pub fn toggle_step_with_velocity(&self, step: Step, velocity: i32) -> Sequence {
+        self.toggle_note_number_at_step(self.default_note_number, velocity, step)
     }
 
     fn has_note_number_at_step(&self, note_number: i32, step: Step) -> bool {
         match self.triggers.get(&step) {
-            Some(t) => t.contains(&Trigger {
-                note_number,
-                offset: 0,
-            }),
+            Some(t) => t.iter().any(|t| t.note_number == note_number),
             None => false,
         }
     }
@@ -129,10 +371,7 @@ impl Sequence {
 
         if let Some(t) = self.triggers.get(&step) {
             let mut step_triggers = t.clone();
-            step_triggers.remove(&Trigger {
-                note_number,
-                offset: 0,
-            });
+            step_triggers.retain(|t| t.note_number != note_number);
             triggers.insert(step, step_triggers);
         }
 
@@ -142,12 +381,8 @@ impl Sequence {
         }
     }
 
-    pub fn current_step(&self, tick: i32) -> Step {
-        let ticks_per_step = 6;
-        let sequence_length_in_ticks = self.number_of_steps * ticks_per_step;
-        let offset_into_sequence = tick % sequence_length_in_ticks;
-
-        Step((offset_into_sequence / ticks_per_step) + 1)
+    pub fn current_step(&self, tick: i32, playback: &Playback) -> Step {
+        self.step_at_position(tick.div_euclid(self.division.ticks_per_step()), playback)
     }
 
     pub fn active_steps(&self) -> HashSet<Step> {
@@ -163,18 +398,18 @@ impl Sequence {
     }
 
     pub fn increment_length(&self) -> Sequence {
-        if self.number_of_steps < 16 {
+        if self.number_of_steps < MAX_STEPS {
             self.set_length(self.number_of_steps + 1)
         } else {
-            self.set_length(16)
+            self.set_length(MAX_STEPS)
         }
     }
 
     pub fn decrement_length(&self) -> Sequence {
-        if self.number_of_steps > 0 {
+        if self.number_of_steps > 1 {
             self.set_length(self.number_of_steps - 1)
         } else {
-            self.set_length(0)
+            self.set_length(1)
         }
     }
 
@@ -198,6 +433,10 @@ impl Sequence {
         }
     }
 
+    pub fn number_of_pages(&self) -> i32 {
+        (self.number_of_steps + STEPS_PER_PAGE - 1) / STEPS_PER_PAGE
+    }
+
     pub fn set_length(&self, number_of_steps: i32) -> Sequence {
         let mut triggers = HashMap::new();
 
@@ -223,8 +462,11 @@ impl Sequence {
     pub fn euclidean_fill(&self, note_number: i32, onsets: i32) -> Sequence {
         let slope = onsets as f32 / self.number_of_steps as f32;
         let mut previous = 1;
-        let mut sequence = Sequence::with_default_note_number(self.default_note_number)
-            .set_length(self.number_of_steps);
+        let mut sequence = Sequence {
+            triggers: HashMap::new(),
+            ..self.clone()
+        }
+        .set_length(self.number_of_steps);
 
         if onsets > 0 {
             for i in 0..self.number_of_steps {
@@ -252,6 +494,93 @@ impl Sequence {
         }
     }
 
+    pub fn clear(&self) -> Sequence {
+        Sequence {
+            triggers: HashMap::new(),
+            ..self.clone()
+        }
+        .set_length(self.number_of_steps)
+    }
+
+    // Doubles the length, playing the steps twice, unless that would make
+    // the sequence too long
+    pub fn double(&self) -> Sequence {
+        match self.number_of_steps * 2 <= MAX_STEPS {
+            true => self.set_length(self.number_of_steps * 2).copy_steps(
+                self,
+                Step(1),
+                Step(self.number_of_steps),
+                Step(self.number_of_steps + 1),
+            ),
+            false => self.clone(),
+        }
+    }
+
+    // Copies the triggers on a range of steps, of this or another sequence,
+    // over the steps from the destination onwards. Steps past the end of the
+    // sequence are left out, and a range that ends before it starts copies
+    // nothing.
+    pub fn copy_steps(
+        &self,
+        source: &Sequence,
+        start: Step,
+        end: Step,
+        destination: Step,
+    ) -> Sequence {
+        let start = start.0.clamp(1, MAX_STEPS);
+        let end = end.0.clamp(1, MAX_STEPS);
+        let destination = destination.0.clamp(1, MAX_STEPS);
+        let mut triggers = self.triggers.clone();
+
+        for (n, step) in (start..=end).enumerate() {
+            let to = Step(destination + n as i32);
+
+            if let (Some(t), true) = (
+                source.triggers.get(&Step(step)),
+                to.0 <= self.number_of_steps,
+            ) {
+                triggers.insert(to, self.copied_triggers(source, t));
+            }
+        }
+
+        Sequence {
+            triggers,
+            ..self.clone()
+        }
+    }
+
+    // Takes the steps and settings of another sequence, keeping the note,
+    // channel and mute of this one
+    pub fn paste(&self, source: &Sequence) -> Sequence {
+        Sequence {
+            triggers: source
+                .triggers
+                .iter()
+                .map(|(step, t)| (*step, self.copied_triggers(source, t)))
+                .collect(),
+            mute: self.mute,
+            default_note_number: self.default_note_number,
+            midi_channel: self.midi_channel,
+            ..source.clone()
+        }
+    }
+
+    // Triggers on the source's default note move to this sequence's default
+    // note, so that a rhythm copied between sequences plays this sequence's
+    // sound
+    fn copied_triggers(&self, source: &Sequence, triggers: &HashSet<Trigger>) -> HashSet<Trigger> {
+        triggers
+            .iter()
+            .map(|t| match t.note_number == source.default_note_number {
+                true => Trigger {
+                    note_number: self.default_note_number,
+                    ..t.clone()
+                },
+                false => t.clone(),
+            })
+            .collect()
+    }
+
     pub fn increment_rotate(&self) -> Sequence {
         self.rotate(1)
     }
@@ -260,6 +589,221 @@ impl Sequence {
         self.rotate(-1)
     }
 
+    pub fn increment_gate(&self) -> Sequence {
+        self.set_gate((self.gate + 1).min(MAX_GATE))
+    }
+
+    pub fn decrement_gate(&self) -> Sequence {
+        self.set_gate((self.gate - 1).max(1))
+    }
+
+    // The gate of steps added from now on. Steps already in the sequence keep
+    // their own.
+    pub fn set_gate(&self, gate: i32) -> Sequence {
+        Sequence {
+            gate,
+            ..self.clone()
+        }
+    }
+
+    pub fn change_step_gate(&self, step: Step, amount: i32) -> Sequence {
+        self.change_triggers_at_step(step, |t| Trigger {
+            length: (t.length + amount).clamp(1, MAX_GATE),
+            ..t.clone()
+        })
+    }
+
+    pub fn nudge_step(&self, step: Step, amount: i32) -> Sequence {
+        let max_offset = self.max_offset();
+
+        self.change_triggers_at_step(step, |t| Trigger {
+            offset: (t.offset + amount).clamp(-max_offset, max_offset),
+            ..t.clone()
+        })
+    }
+
+    fn max_offset(&self) -> i32 {
+        self.division.ticks_per_step() / 2
+    }
+
+    pub fn increment_direction(&self) -> Sequence {
+        self.set_direction(self.direction.next())
+    }
+
+    pub fn decrement_direction(&self) -> Sequence {
+        self.set_direction(self.direction.previous())
+    }
+
+    pub fn set_direction(&self, direction: Direction) -> Sequence {
+        Sequence {
+            direction,
+            ..self.clone()
+        }
+    }
+
+    pub fn increment_division(&self) -> Sequence {
+        self.set_division(self.division.next())
+    }
+
+    pub fn decrement_division(&self) -> Sequence {
+        self.set_division(self.division.previous())
+    }
+
+    pub fn set_division(&self, division: Division) -> Sequence {
+        let sequence = Sequence {
+            division,
+            ..self.clone()
+        };
+        let max_offset = sequence.max_offset();
+
+        sequence.change_triggers(|_, t| Trigger {
+            offset: t.offset.clamp(-max_offset, max_offset),
+            ..t.clone()
+        })
+    }
+
+    pub fn increment_ratchets(&self, step: Step) -> Sequence {
+        self.change_triggers_at_step(step, |t| {
+            let ratchets = RATCHETS
+                .iter()
+                .find(|r| **r > t.ratchets)
+                .unwrap_or(&t.ratchets);
+
+            Trigger {
+                ratchets: *ratchets,
+                ..t.clone()
+            }
+        })
+    }
+
+    pub fn decrement_ratchets(&self, step: Step) -> Sequence {
+        self.change_triggers_at_step(step, |t| {
+            let ratchets = RATCHETS
+                .iter()
+                .rev()
+                .find(|r| **r < t.ratchets)
+                .unwrap_or(&t.ratchets);
+
+            Trigger {
+                ratchets: *ratchets,
+                ..t.clone()
+            }
+        })
+    }
+
+    pub fn increment_ramp(&self, step: Step) -> Sequence {
+        self.change_triggers_at_step(step, |t| Trigger {
+            ramp: match t.ramp {
+                Ramp::Down => Ramp::Flat,
+                _ => Ramp::Up,
+            },
+            ..t.clone()
+        })
+    }
+
+    pub fn decrement_ramp(&self, step: Step) -> Sequence {
+        self.change_triggers_at_step(step, |t| Trigger {
+            ramp: match t.ramp {
+                Ramp::Up => Ramp::Flat,
+                _ => Ramp::Down,
+            },
+            ..t.clone()
+        })
+    }
+
+    pub fn increment_condition(&self, step: Step) -> Sequence {
+        self.change_triggers_at_step(step, |t| Trigger {
+            condition: t.condition.next(),
+            ..t.clone()
+        })
+    }
+
+    pub fn decrement_condition(&self, step: Step) -> Sequence {
+        self.change_triggers_at_step(step, |t| Trigger {
+            condition: t.condition.previous(),
+            ..t.clone()
+        })
+    }
+
+    fn change_triggers_at_step<F>(&self, step: Step, f: F) -> Sequence
+    where
+        F: Fn(&Trigger) -> Trigger,
+    {
+        self.change_triggers(|s, t| match *s == step {
+            true => f(t),
+            false => t.clone(),
+        })
+    }
+
+    fn change_triggers<F>(&self, f: F) -> Sequence
+    where
+        F: Fn(&Step, &Trigger) -> Trigger,
+    {
+        let triggers = self
+            .triggers
+            .iter()
+            .map(|(s, t)| (*s, t.iter().map(|t| f(s, t)).collect()))
+            .collect();
+
+        Sequence {
+            triggers,
+            ..self.clone()
+        }
+    }
+
+    // Brings values a hand edited project file may have got wrong back into
+    // the range the sequence can play
+    pub fn normalise(&self) -> Sequence {
+        self.set_division(Division(self.division.0.max(1)))
+            .set_length(self.number_of_steps.clamp(1, MAX_STEPS))
+            .change_triggers(|_, t| Trigger {
+                ratchets: t.ratchets.max(1),
+                ..t.clone()
+            })
+    }
+
+    pub fn increment_swing(&self) -> Sequence {
+        let swing_amount = match self.swing_amount {
+            Some(amount) => (amount + 1).min(swing::MAX_AMOUNT),
+            None => swing::MIN_AMOUNT,
+        };
+
+        Sequence {
+            swing_amount: Some(swing_amount),
+            ..self.clone()
+        }
+    }
+
+    pub fn decrement_swing(&self) -> Sequence {
+        let swing_amount = match self.swing_amount {
+            Some(amount) if amount > swing::MIN_AMOUNT => Some(amount - 1),
+            _ => None,
+        };
+
+        Sequence {
+            swing_amount,
+            ..self.clone()
+        }
+    }
+
+    pub fn midi_channel(&self) -> i32 {
+        self.midi_channel
+    }
+
+    pub fn number_of_steps(&self) -> i32 {
+        self.number_of_steps
+    }
+
+    pub fn division(&self) -> Division {
+        self.division
+    }
+
+    // The number of ticks before the sequence repeats, ignoring conditions
+    // and random directions
+    pub fn loop_length_in_ticks(&self) -> i32 {
+        self.direction.loop_length(self.number_of_steps) * self.division.ticks_per_step()
+    }
+
     pub fn toggle_mute(&self) -> Sequence {
         Sequence {
             mute: !self.mute,
@@ -267,17 +811,25 @@ impl Sequence {
         }
     }
 
+    pub fn set_mute(&self, mute: bool) -> Sequence {
+        Sequence {
+            mute,
+            ..self.clone()
+        }
+    }
+
     pub fn is_muted(&self) -> bool {
         self.mute
     }
 }
 
 #[test]
+#[allow(clippy::if_same_then_else)]
 fn test_adding_trigger_to_sequence() {
     let sequence = Sequence::empty().trigger_note_number_at_step(1, Step(1));
 
     for n in 0..=96 {
-        let triggers = sequence.triggers_for_tick(n);
+        let triggers = sequence.triggers_for_tick(n, &Playback::default());
         if n == 0 {
             assert_eq!(1, triggers.len());
         } else if n == 96 {
@@ -294,7 +846,7 @@ fn test_adding_two_triggers_to_sequence() {
         .trigger_note_number_at_step(1, Step(1))
         .trigger_note_number_at_step(2, Step(1));
 
-    let triggers = sequence.triggers_for_tick(0);
+    let triggers = sequence.triggers_for_tick(0, &Playback::default());
     assert_eq!(2, triggers.len());
 }
 
@@ -304,7 +856,7 @@ fn test_adding_the_same_trigger_twice_to_sequence() {
         .trigger_note_number_at_step(1, Step(1))
         .trigger_note_number_at_step(1, Step(1));
 
-    let triggers = sequence.triggers_for_tick(0);
+    let triggers = sequence.triggers_for_tick(0, &Playback::default());
     assert_eq!(1, triggers.len());
 }
 
@@ -312,11 +864,11 @@ fn test_adding_the_same_trigger_twice_to_sequence() {
 fn test_current_step() {
     let sequencer = Sequence::empty();
 
-    assert_eq!(Step(1), sequencer.current_step(0));
-    assert_eq!(Step(1), sequencer.current_step(1));
-    assert_eq!(Step(2), sequencer.current_step(6));
-    assert_eq!(Step(16), sequencer.current_step(95));
-    assert_eq!(Step(1), sequencer.current_step(96));
+    assert_eq!(Step(1), sequencer.current_step(0, &Playback::default()));
+    assert_eq!(Step(1), sequencer.current_step(1, &Playback::default()));
+    assert_eq!(Step(2), sequencer.current_step(6, &Playback::default()));
+    assert_eq!(Step(16), sequencer.current_step(95, &Playback::default()));
+    assert_eq!(Step(1), sequencer.current_step(96, &Playback::default()));
 }
 
 #[test]
@@ -363,10 +915,22 @@ fn test_set_length_longer() {
 
     // Check we can still iterate over sequence when made longer
     for n in 0..=96 {
-        sequence.triggers_for_tick(n);
+        sequence.triggers_for_tick(n, &Playback::default());
     }
 }
 
+#[test]
+fn test_euclidean_fill_keeps_settings() {
+    let sequence = Sequence::empty()
+        .with_midi_channel(5)
+        .set_division(Division::eighth())
+        .euclidean_fill(1, 4);
+
+    assert_eq!(5, sequence.midi_channel());
+    assert_eq!(Division::eighth(), sequence.division());
+    assert_eq!(4, sequence.active_steps().len());
+}
+
 #[test]
 fn test_euclidean_fill() {
     let sequence = Sequence::empty().set_length(16).euclidean_fill(1, 4);
@@ -425,7 +989,7 @@ fn test_toggle_mute() {
         .toggle_mute();
 
     for n in 0..=96 {
-        let triggers = sequence.triggers_for_tick(n);
+        let triggers = sequence.triggers_for_tick(n, &Playback::default());
         assert!(triggers.is_empty());
     }
 }
@@ -436,8 +1000,15 @@ fn test_toggle_step() {
 
     assert_eq!(1, sequence.toggle_step(Step(1)).active_steps().len());
     assert_eq!(
-        Event { note_number: 37 },
-        sequence.toggle_step(Step(1)).events_for_tick(0)[0]
+        Event {
+            note_number: 37,
+            velocity: 127,
+            length: 6,
+            channel: 1,
+        },
+        sequence
+            .toggle_step(Step(1))
+            .events_for_tick(0, &Playback::default())[0]
     );
     assert_eq!(
         0,
@@ -451,6 +1022,673 @@ fn test_toggle_step() {
 
 #[test]
 fn test_default_event() {
-    let event = Sequence::with_default_note_number(37).default_event();
-    assert_eq!(Event { note_number: 37 }, event)
+    let event = Sequence::with_default_note_number(37)
+        .with_midi_channel(3)
+        .default_event();
+    assert_eq!(
+        Event {
+            note_number: 37,
+            velocity: 127,
+            length: 6,
+            channel: 3,
+        },
+        event
+    )
+}
+
+#[test]
+fn test_toggle_step_with_velocity() {
+    let sequence = Sequence::with_default_note_number(37).toggle_step_with_velocity(Step(1), 64);
+
+    assert_eq!(
+        64,
+        sequence.events_for_tick(0, &Playback::default())[0].velocity
+    );
+    assert_eq!(
+        0,
+        sequence
+            .toggle_step_with_velocity(Step(1), 100)
+            .active_steps()
+            .len()
+    );
+}
+
+#[test]
+fn test_retriggering_note_number_replaces_velocity() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step_with_velocity(1, 30, Step(1))
+        .trigger_note_number_at_step_with_velocity(1, 90, Step(1));
+
+    let triggers = sequence.triggers_for_tick(0, &Playback::default());
+    assert_eq!(1, triggers.len());
+    assert_eq!(90, triggers[0].velocity);
+}
+
+#[test]
+fn test_set_gate() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(1))
+        .set_gate(3);
+
+    assert_eq!(
+        DEFAULT_LENGTH,
+        sequence.events_for_tick(0, &Playback::default())[0].length
+    );
+    assert_eq!(
+        3,
+        sequence
+            .trigger_note_number_at_step(1, Step(2))
+            .events_for_tick(6, &Playback::default())[0]
+            .length
+    );
+}
+
+#[test]
+fn test_gate_limits() {
+    assert_eq!(1, Sequence::empty().set_gate(1).decrement_gate().gate);
+    assert_eq!(96, Sequence::empty().set_gate(96).increment_gate().gate);
+}
+
+#[test]
+fn test_change_step_gate() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(1))
+        .trigger_note_number_at_step(1, Step(2))
+        .change_step_gate(Step(2), 3);
+
+    assert_eq!(
+        DEFAULT_LENGTH,
+        sequence.events_for_tick(0, &Playback::default())[0].length
+    );
+    assert_eq!(
+        DEFAULT_LENGTH + 3,
+        sequence.events_for_tick(6, &Playback::default())[0].length
+    );
+
+    let sequence = sequence.change_step_gate(Step(2), -100);
+    assert_eq!(
+        1,
+        sequence.events_for_tick(6, &Playback::default())[0].length
+    );
+    let sequence = sequence.change_step_gate(Step(2), 200);
+    assert_eq!(
+        MAX_GATE,
+        sequence.events_for_tick(6, &Playback::default())[0].length
+    );
+}
+
+#[test]
+fn test_nudge_step() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(2))
+        .nudge_step(Step(2), 2);
+
+    assert!(sequence
+        .triggers_for_tick(6, &Playback::default())
+        .is_empty());
+    assert_eq!(1, sequence.triggers_for_tick(8, &Playback::default()).len());
+
+    let sequence = sequence.nudge_step(Step(2), -4);
+    assert!(sequence
+        .triggers_for_tick(8, &Playback::default())
+        .is_empty());
+    assert_eq!(1, sequence.triggers_for_tick(4, &Playback::default()).len());
+}
+
+#[test]
+fn test_nudge_step_limits() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(2))
+        .nudge_step(Step(2), 10);
+    assert_eq!(1, sequence.triggers_for_tick(9, &Playback::default()).len());
+
+    let sequence = sequence.nudge_step(Step(2), -20);
+    assert_eq!(1, sequence.triggers_for_tick(3, &Playback::default()).len());
+}
+
+#[test]
+fn test_nudge_first_step_earlier_wraps_to_end_of_sequence() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(1))
+        .nudge_step(Step(1), -1);
+
+    assert!(sequence
+        .triggers_for_tick(0, &Playback::default())
+        .is_empty());
+    assert_eq!(
+        1,
+        sequence.triggers_for_tick(95, &Playback::default()).len()
+    );
+    assert_eq!(
+        1,
+        sequence.triggers_for_tick(191, &Playback::default()).len()
+    );
+}
+
+#[test]
+fn test_toggle_nudged_step() {
+    let sequence = Sequence::with_default_note_number(37)
+        .toggle_step(Step(3))
+        .nudge_step(Step(3), -2);
+
+    assert!(sequence.has_note_number_at_step(37, Step(3)));
+    assert!(sequence.toggle_step(Step(3)).active_steps().is_empty());
+    assert!(sequence
+        .remove_note_number_at_step(37, Step(3))
+        .active_steps()
+        .is_empty());
+}
+
+#[test]
+fn test_global_swing() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(1))
+        .trigger_note_number_at_step(1, Step(2));
+    let playback = Playback {
+        swing: Swing::default().with_amount(66),
+        ..Playback::default()
+    };
+
+    assert_eq!(1, sequence.triggers_for_tick(0, &playback).len());
+    assert!(sequence.triggers_for_tick(6, &playback).is_empty());
+    assert_eq!(1, sequence.triggers_for_tick(8, &playback).len());
+}
+
+#[test]
+fn test_swing_with_nudged_step() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(2))
+        .nudge_step(Step(2), 3);
+    let playback = Playback {
+        swing: Swing::default()
+            .with_amount(75)
+            .set_resolution(swing::Resolution::Eighth),
+        ..Playback::default()
+    };
+
+    // step 2 is not on the eighth note off-beat so is not swung
+    assert_eq!(1, sequence.triggers_for_tick(9, &playback).len());
+
+    let sequence = sequence.rotate(1);
+    assert_eq!(1, sequence.triggers_for_tick(21, &playback).len());
+}
+
+#[test]
+fn test_sequence_swing_overrides_global_swing() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(2))
+        .increment_swing();
+    let playback = Playback {
+        swing: Swing::default().with_amount(66),
+        ..Playback::default()
+    };
+
+    assert_eq!(Some(50), sequence.swing_amount);
+    assert_eq!(1, sequence.triggers_for_tick(6, &playback).len());
+
+    let sequence = sequence.decrement_swing();
+    assert_eq!(None, sequence.swing_amount);
+    assert_eq!(1, sequence.triggers_for_tick(8, &playback).len());
+}
+
+#[cfg(test)]
+fn set_condition(sequence: &Sequence, step: Step, condition: Condition) -> Sequence {
+    sequence.change_triggers_at_step(step, |t| Trigger {
+        condition,
+        ..t.clone()
+    })
+}
+
+#[test]
+fn test_ratio_condition() {
+    let sequence = set_condition(
+        &Sequence::empty()
+            .set_length(4)
+            .trigger_note_number_at_step(1, Step(1)),
+        Step(1),
+        Condition::Ratio(2, 3),
+    );
+    let playback = Playback::default();
+
+    let loops_played: Vec<bool> = (0..6)
+        .map(|n| !sequence.triggers_for_tick(n * 24, &playback).is_empty())
+        .collect();
+
+    assert_eq!(vec![false, true, false, false, true, false], loops_played);
+}
+
+#[test]
+fn test_fill_condition() {
+    let sequence = set_condition(
+        &Sequence::empty().trigger_note_number_at_step(1, Step(1)),
+        Step(1),
+        Condition::Fill,
+    );
+    let fill = Playback {
+        fill: true,
+        ..Playback::default()
+    };
+
+    assert!(sequence
+        .triggers_for_tick(0, &Playback::default())
+        .is_empty());
+    assert_eq!(1, sequence.triggers_for_tick(0, &fill).len());
+}
+
+#[test]
+fn test_not_first_condition() {
+    let sequence = set_condition(
+        &Sequence::empty().trigger_note_number_at_step(1, Step(1)),
+        Step(1),
+        Condition::NotFirst,
+    );
+    let playback = Playback::default();
+
+    assert!(sequence.triggers_for_tick(0, &playback).is_empty());
+    assert_eq!(1, sequence.triggers_for_tick(96, &playback).len());
+}
+
+#[test]
+fn test_probability_condition_is_deterministic_for_seed() {
+    let sequence = set_condition(
+        &Sequence::empty()
+            .set_length(1)
+            .trigger_note_number_at_step(1, Step(1)),
+        Step(1),
+        Condition::Probability(50),
+    );
+    let playback = Playback {
+        seed: 1234,
+        ..Playback::default()
+    };
+
+    let played = |p: &Playback| -> Vec<bool> {
+        (0..100)
+            .map(|n| !sequence.triggers_for_tick(n * 6, p).is_empty())
+            .collect()
+    };
+
+    let count = played(&playback).iter().filter(|p| **p).count();
+    assert!(count > 30 && count < 70);
+    assert_eq!(played(&playback), played(&playback));
+    assert_ne!(
+        played(&playback),
+        played(&Playback {
+            seed: 4321,
+            ..playback
+        })
+    );
+}
+
+#[test]
+fn test_previous_condition() {
+    let sequence = Sequence::empty()
+        .set_length(2)
+        .trigger_note_number_at_step(1, Step(1))
+        .trigger_note_number_at_step(2, Step(2));
+    let sequence = set_condition(&sequence, Step(1), Condition::Ratio(1, 2));
+    let previous = set_condition(&sequence, Step(2), Condition::Previous);
+    let not_previous = set_condition(&sequence, Step(2), Condition::NotPrevious);
+    let playback = Playback::default();
+
+    // step 1 plays on the first of every two loops, and each loop is 12
+    // ticks long
+    assert_eq!(1, previous.triggers_for_tick(6, &playback).len());
+    assert!(previous.triggers_for_tick(18, &playback).is_empty());
+    assert!(not_previous.triggers_for_tick(6, &playback).is_empty());
+    assert_eq!(1, not_previous.triggers_for_tick(18, &playback).len());
+}
+
+#[test]
+fn test_increment_condition() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(1))
+        .increment_condition(Step(1));
+
+    // the first condition after Always is a 1% probability
+    let played = (0..100)
+        .filter(|n| {
+            !sequence
+                .triggers_for_tick(n * 96, &Playback::default())
+                .is_empty()
+        })
+        .count();
+    assert!(played < 10);
+
+    let sequence = sequence.decrement_condition(Step(1));
+    assert_eq!(1, sequence.triggers_for_tick(0, &Playback::default()).len());
+}
+
+#[test]
+fn test_ratchets() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(2))
+        .increment_ratchets(Step(2))
+        .increment_ratchets(Step(2));
+    let playback = Playback::default();
+
+    let hits: Vec<i32> = (0..96)
+        .filter(|n| !sequence.triggers_for_tick(*n, &playback).is_empty())
+        .collect();
+
+    assert_eq!(vec![6, 8, 10], hits);
+    assert_eq!(2, sequence.triggers_for_tick(6, &playback)[0].length);
+}
+
+#[test]
+fn test_ratchet_limits() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(1))
+        .decrement_ratchets(Step(1));
+    let playback = Playback::default();
+
+    assert_eq!(
+        1,
+        (0..6)
+            .filter(|n| !sequence.triggers_for_tick(*n, &playback).is_empty())
+            .count()
+    );
+
+    let mut sequence = sequence;
+    for _ in 0..10 {
+        sequence = sequence.increment_ratchets(Step(1));
+    }
+
+    assert_eq!(
+        6,
+        (0..6)
+            .filter(|n| !sequence.triggers_for_tick(*n, &playback).is_empty())
+            .count()
+    );
+}
+
+#[test]
+fn test_ratchets_at_fine_division() {
+    let mut sequence = Sequence::empty()
+        .set_division(Division(3))
+        .trigger_note_number_at_step_with_velocity(1, 100, Step(1));
+    for _ in 0..4 {
+        sequence = sequence.increment_ratchets(Step(1)).increment_ramp(Step(1));
+    }
+    let playback = Playback::default();
+
+    // six ratchets at 1/32 play once on each of the step's three ticks
+    let hits: Vec<Vec<i32>> = (0..6)
+        .map(|n| {
+            sequence
+                .triggers_for_tick(n, &playback)
+                .iter()
+                .map(|t| t.velocity)
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        vec![vec![33], vec![66], vec![100], vec![], vec![], vec![]],
+        hits
+    );
+    assert_eq!(1, sequence.triggers_for_tick(0, &playback)[0].length);
+}
+
+#[test]
+fn test_ratchets_with_velocity_ramp() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step_with_velocity(1, 100, Step(1))
+        .increment_ratchets(Step(1))
+        .increment_ratchets(Step(1))
+        .increment_ratchets(Step(1));
+    let playback = Playback::default();
+
+    let velocities = |s: &Sequence| -> Vec<i32> {
+        (0..6)
+            .flat_map(|n| s.triggers_for_tick(n, &playback))
+            .map(|t| t.velocity)
+            .collect()
+    };
+
+    assert_eq!(vec![100, 100, 100, 100], velocities(&sequence));
+    assert_eq!(
+        vec![25, 50, 75, 100],
+        velocities(&sequence.increment_ramp(Step(1)))
+    );
+    assert_eq!(
+        vec![100, 75, 50, 25],
+        velocities(&sequence.decrement_ramp(Step(1)))
+    );
+}
+
+#[test]
+fn test_ratchets_share_condition() {
+    let sequence = set_condition(
+        &Sequence::empty()
+            .set_length(1)
+            .trigger_note_number_at_step(1, Step(1))
+            .increment_ratchets(Step(1)),
+        Step(1),
+        Condition::Probability(50),
+    );
+    let playback = Playback::default();
+
+    for n in 0..50 {
+        assert_eq!(
+            sequence.triggers_for_tick(n * 6, &playback).len(),
+            sequence.triggers_for_tick(n * 6 + 3, &playback).len()
+        );
+    }
+}
+
+#[test]
+fn test_division() {
+    let sequence = Sequence::empty()
+        .set_length(4)
+        .trigger_note_number_at_step(1, Step(1))
+        .trigger_note_number_at_step(1, Step(2))
+        .set_division(Division::eighth());
+    let playback = Playback::default();
+
+    let hits: Vec<i32> = (0..96)
+        .filter(|n| !sequence.triggers_for_tick(*n, &playback).is_empty())
+        .collect();
+
+    assert_eq!(vec![0, 12, 48, 60], hits);
+    assert_eq!(Step(1), sequence.current_step(11, &Playback::default()));
+    assert_eq!(Step(2), sequence.current_step(12, &Playback::default()));
+    assert_eq!(Step(1), sequence.current_step(48, &Playback::default()));
+}
+
+#[test]
+fn test_triplet_division() {
+    let sequence = Sequence::empty()
+        .set_length(3)
+        .trigger_note_number_at_step(1, Step(1))
+        .trigger_note_number_at_step(1, Step(2))
+        .trigger_note_number_at_step(1, Step(3))
+        .set_division(Division::eighth_triplet());
+    let playback = Playback::default();
+
+    let hits: Vec<i32> = (0..48)
+        .filter(|n| !sequence.triggers_for_tick(*n, &playback).is_empty())
+        .collect();
+
+    assert_eq!(vec![0, 8, 16, 24, 32, 40], hits);
+}
+
+#[test]
+fn test_set_division_limits_offsets() {
+    let sequence = Sequence::empty()
+        .trigger_note_number_at_step(1, Step(2))
+        .set_division(Division::eighth())
+        .nudge_step(Step(2), 6);
+    let playback = Playback::default();
+
+    assert_eq!(1, sequence.triggers_for_tick(18, &playback).len());
+
+    let sequence = sequence.set_division(Division::sixteenth());
+    assert_eq!(1, sequence.triggers_for_tick(9, &playback).len());
+}
+
+#[test]
+fn test_increment_length_beyond_sixteen_steps() {
+    let mut sequence = Sequence::empty();
+    for _ in 0..100 {
+        sequence = sequence.increment_length();
+    }
+
+    assert_eq!(MAX_STEPS, sequence.number_of_steps);
+    assert_eq!(4, sequence.number_of_pages());
+    assert_eq!(Step(64), sequence.current_step(383, &Playback::default()));
+
+    let sequence = sequence.toggle_step(Step(40));
+    assert_eq!(1, sequence.events_for_tick(234, &Playback::default()).len());
+}
+
+#[test]
+fn test_step_pages() {
+    assert_eq!(0, Step(16).page());
+    assert_eq!(1, Step(17).page());
+    assert_eq!(1, Step(17).pad());
+    assert_eq!(16, Step(32).pad());
+    assert_eq!(Step(35), Step::on_page(2, 3));
+}
+
+#[test]
+fn test_number_of_pages() {
+    assert_eq!(1, Sequence::empty().set_length(1).number_of_pages());
+    assert_eq!(1, Sequence::empty().number_of_pages());
+    assert_eq!(2, Sequence::empty().set_length(17).number_of_pages());
+}
+
+#[test]
+fn test_reverse_direction() {
+    let sequence = Sequence::empty()
+        .set_length(4)
+        .trigger_note_number_at_step(1, Step(1))
+        .increment_direction();
+    let playback = Playback::default();
+
+    assert_eq!(Step(4), sequence.current_step(0, &playback));
+    assert_eq!(Step(1), sequence.current_step(18, &playback));
+    assert!(sequence.triggers_for_tick(0, &playback).is_empty());
+    assert_eq!(1, sequence.triggers_for_tick(18, &playback).len());
+}
+
+#[test]
+fn test_ping_pong_direction() {
+    let sequence = Sequence::empty()
+        .set_length(4)
+        .trigger_note_number_at_step(1, Step(3))
+        .set_direction(Direction::PingPong);
+    let playback = Playback::default();
+
+    let hits: Vec<i32> = (0..60)
+        .filter(|n| !sequence.triggers_for_tick(*n, &playback).is_empty())
+        .collect();
+
+    // 1 2 3 4 3 2 1 2 3 4
+    assert_eq!(vec![12, 24, 48], hits);
+}
+
+#[test]
+fn test_random_direction_current_step_matches_triggers() {
+    let mut sequence = Sequence::empty().set_direction(Direction::Random);
+    for n in 1..=16 {
+        sequence = sequence.trigger_note_number_at_step(n, Step(n));
+    }
+    let playback = Playback {
+        seed: 99,
+        ..Playback::default()
+    };
+
+    for tick in (0..960).step_by(6) {
+        let triggers = sequence.triggers_for_tick(tick, &playback);
+        assert_eq!(1, triggers.len());
+        assert_eq!(
+            sequence.current_step(tick, &playback).0,
+            triggers[0].note_number
+        );
+    }
+}
+
+#[test]
+fn test_clear() {
+    let sequence = Sequence::empty()
+        .set_length(20)
+        .toggle_step(Step(1))
+        .toggle_step(Step(20))
+        .clear();
+
+    assert!(sequence.active_steps().is_empty());
+    assert_eq!(20, sequence.number_of_steps());
+    assert!(sequence
+        .events_for_tick(19 * 6, &Playback::default())
+        .is_empty());
+}
+
+#[test]
+fn test_double() {
+    let sequence = Sequence::empty()
+        .set_length(3)
+        .toggle_step(Step(1))
+        .toggle_step(Step(3))
+        .double();
+
+    assert_eq!(6, sequence.number_of_steps());
+    let mut steps: Vec<Step> = sequence.active_steps().into_iter().collect();
+    steps.sort_by_key(|s| s.0);
+    assert_eq!(vec![Step(1), Step(3), Step(4), Step(6)], steps);
+
+    let too_long = Sequence::empty().set_length(40);
+    assert_eq!(40, too_long.double().number_of_steps());
+}
+
+#[test]
+fn test_copy_steps() {
+    let sequence = Sequence::empty()
+        .toggle_step(Step(1))
+        .toggle_step(Step(2))
+        .toggle_step(Step(6));
+
+    let copied = sequence.copy_steps(&sequence, Step(1), Step(4), Step(5));
+    let mut steps: Vec<Step> = copied.active_steps().into_iter().collect();
+    steps.sort_by_key(|s| s.0);
+    assert_eq!(vec![Step(1), Step(2), Step(5), Step(6)], steps);
+
+    // steps past the end are left out
+    let copied = sequence.copy_steps(&sequence, Step(1), Step(2), Step(16));
+    assert!(copied.active_steps().contains(&Step(16)));
+    assert_eq!(16, copied.number_of_steps());
+}
+
+#[test]
+fn test_copy_steps_out_of_range() {
+    let sequence = Sequence::empty().toggle_step(Step(1));
+
+    assert_eq!(
+        sequence,
+        sequence.copy_steps(&sequence, Step(4), Step(1), Step(8))
+    );
+
+    let copied = sequence.copy_steps(&sequence, Step(i32::MIN), Step(i32::MAX), Step(i32::MAX));
+    assert_eq!(sequence, copied);
+
+    let copied = sequence.copy_steps(&sequence, Step(-3), Step(1), Step(0));
+    assert_eq!(sequence, copied);
+}
+
+#[test]
+fn test_paste_keeps_note_and_channel() {
+    let kick = Sequence::with_default_note_number(36)
+        .with_midi_channel(1)
+        .set_length(8)
+        .toggle_step(Step(1));
+    let snare = Sequence::with_default_note_number(38)
+        .with_midi_channel(3)
+        .toggle_mute()
+        .paste(&kick);
+
+    assert_eq!(8, snare.number_of_steps());
+    assert!(snare.is_muted());
+
+    let events = snare.toggle_mute().events_for_tick(0, &Playback::default());
+    assert_eq!(38, events[0].note_number);
+    assert_eq!(3, events[0].channel);
 }
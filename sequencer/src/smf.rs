@@ -0,0 +1,570 @@
+use crate::context::Context;
+use crate::division::Division;
+use crate::event::DEFAULT_LENGTH;
+use crate::pattern::MAX_SEQUENCES;
+use crate::sequence::{Sequence, Step, MAX_STEPS};
+use crate::time_signature::TimeSignature;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Ticks per quarter note, the same resolution the sequencer runs at
+const PPQN: u16 = 24;
+
+// The longest export, to keep sequences of unusual lengths from making huge
+// files
+const MAX_BARS: i32 = 64;
+
+// Renders the sequences to a type 1 Standard MIDI File, with a conductor
+// track for tempo and time signature followed by one track per sequence.
+// The export is long enough for the longest sequence to play through once,
+// rounded up to a whole bar.
+pub fn export(context: &Context) -> Vec<u8> {
+    let ticks_per_bar = context.time_signature.ticks_per_bar();
+    let longest = context
+        .sequences
+        .iter()
+        .map(|s| s.loop_length_in_ticks())
+        .max()
+        .unwrap_or(ticks_per_bar);
+    let bars = ((longest + ticks_per_bar - 1) / ticks_per_bar).clamp(1, MAX_BARS);
+    let length = bars * ticks_per_bar;
+
+    let mut tracks = vec![conductor_track(context)];
+    for (n, sequence) in context.sequences.iter().enumerate() {
+        tracks.push(sequence_track(context, n, sequence, length));
+    }
+
+    let mut file = header(tracks.len() as u16);
+    for track in tracks {
+        file.extend(track);
+    }
+    file
+}
+
+fn header(number_of_tracks: u16) -> Vec<u8> {
+    let mut header = b"MThd".to_vec();
+    header.extend(&6u32.to_be_bytes());
+    header.extend(&1u16.to_be_bytes());
+    header.extend(&number_of_tracks.to_be_bytes());
+    header.extend(&PPQN.to_be_bytes());
+    header
+}
+
+fn conductor_track(context: &Context) -> Vec<u8> {
+    let microseconds_per_quarter = (60_000_000.0 / context.bpm) as u32;
+    let time_signature = context.time_signature;
+    let denominator = (time_signature.unit as u32).trailing_zeros() as u8;
+
+    let mut tempo = vec![0xff, 0x51, 0x03];
+    tempo.extend(&microseconds_per_quarter.to_be_bytes()[1..]);
+
+    track(vec![
+        (0, tempo),
+        (
+            0,
+            vec![
+                0xff,
+                0x58,
+                0x04,
+                time_signature.beats as u8,
+                denominator,
+                PPQN as u8,
+                8,
+            ],
+        ),
+    ])
+}
+
+fn sequence_track(context: &Context, number: usize, sequence: &Sequence, length: i32) -> Vec<u8> {
+    let playback = context.playback();
+    let channel = (sequence.midi_channel() - 1).clamp(0, 15) as u8;
+    let name = format!("Sequence {}", number + 1);
+
+    let mut name_event = vec![0xff, 0x03];
+    name_event.extend(variable_length(name.len() as u32));
+    name_event.extend(name.as_bytes());
+
+    let mut events = vec![(0, name_event)];
+    for tick in 0..length {
+        for event in sequence.events_for_tick(tick, &playback) {
+            let note = event.note_number.clamp(0, 127) as u8;
+            let velocity = event.velocity.clamp(1, 127) as u8;
+
+            events.push((tick, vec![0x90 | channel, note, velocity]));
+            events.push((tick + event.length, vec![0x80 | channel, note, 0]));
+        }
+    }
+
+    track(events)
+}
+
+// Sorts events by tick, with note offs before note ons on the same tick so
+// that repeated notes aren't cut short, and writes them as a track chunk
+fn track(mut events: Vec<(i32, Vec<u8>)>) -> Vec<u8> {
+    events.sort_by_key(|(tick, bytes)| (*tick, bytes[0] & 0xf0 != 0x80));
+
+    let mut data = vec![];
+    let mut last_tick = 0;
+    for (tick, bytes) in events {
+        data.extend(variable_length((tick - last_tick) as u32));
+        data.extend(bytes);
+        last_tick = tick;
+    }
+    data.extend(&[0x00, 0xff, 0x2f, 0x00]);
+
+    let mut chunk = b"MTrk".to_vec();
+    chunk.extend(&(data.len() as u32).to_be_bytes());
+    chunk.extend(data);
+    chunk
+}
+
+fn variable_length(value: u32) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut value = value >> 7;
+
+    while value > 0 {
+        bytes.insert(0, (value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ImportOptions {
+    // The grid notes are quantized to, which becomes the division of each
+    // imported sequence
+    pub division: Division,
+    pub time_signature: TimeSignature,
+    // One sequence per track rather than one per note number, for melodic
+    // parts
+    pub by_track: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub sequences: Vec<Sequence>,
+    // Notes that didn't fit, as human readable descriptions
+    pub dropped: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    NotAMidiFile,
+    UnsupportedTiming,
+    Truncated,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotAMidiFile => write!(f, "not a Standard MIDI File"),
+            Error::UnsupportedTiming => write!(f, "SMPTE timing is not supported"),
+            Error::Truncated => write!(f, "the file ends unexpectedly"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Note {
+    track: usize,
+    // From 1, like the sequences
+    channel: i32,
+    note_number: i32,
+    velocity: i32,
+    // In ticks at the sequencer's resolution
+    start: i32,
+    length: i32,
+}
+
+// Quantizes the notes in a MIDI file to the nearest step of the grid, keeping
+// the distance from the step as the trigger's offset. Each sequence plays on
+// the channel of its first note.
+pub fn import(bytes: &[u8], options: ImportOptions) -> Result<Import, Error> {
+    let notes = parse(bytes)?;
+    let division = Division(options.division.ticks_per_step().max(1));
+    let ticks_per_step = division.ticks_per_step();
+    let steps_per_bar = options.time_signature.steps_per_bar(division);
+
+    let mut groups: BTreeMap<(usize, i32), Vec<Note>> = BTreeMap::new();
+    for note in notes {
+        let key = match options.by_track {
+            true => (note.track, 0),
+            false => (note.channel as usize, note.note_number),
+        };
+        groups.entry(key).or_default().push(note);
+    }
+
+    let last_position = groups
+        .values()
+        .flatten()
+        .map(|n| position(n.start, ticks_per_step))
+        .max()
+        .unwrap_or(0);
+    let bars = (last_position + steps_per_bar) / steps_per_bar;
+    let number_of_steps = (bars * steps_per_bar).clamp(1, MAX_STEPS);
+
+    let mut sequences = vec![];
+    let mut dropped = vec![];
+
+    for (n, notes) in groups.values().enumerate() {
+        if n >= MAX_SEQUENCES {
+            for note in notes {
+                dropped.push(format!("{} (no sequence left for it)", describe(note)));
+            }
+            continue;
+        }
+
+        let mut sequence = Sequence::with_default_note_number(notes[0].note_number)
+            .with_midi_channel(notes[0].channel)
+            .set_division(division)
+            .set_length(number_of_steps);
+
+        for note in notes {
+            let position = position(note.start, ticks_per_step);
+
+            match position < number_of_steps {
+                true => {
+                    sequence = sequence.add_note(
+                        Step(position + 1),
+                        note.note_number,
+                        note.velocity,
+                        note.length.max(1),
+                        note.start - position * ticks_per_step,
+                    )
+                }
+                false => dropped.push(format!("{} (past step {})", describe(note), MAX_STEPS)),
+            }
+        }
+
+        sequences.push(sequence);
+    }
+
+    Ok(Import { sequences, dropped })
+}
+
+fn position(tick: i32, ticks_per_step: i32) -> i32 {
+    (tick + ticks_per_step / 2).div_euclid(ticks_per_step)
+}
+
+fn describe(note: &Note) -> String {
+    format!(
+        "note {} at tick {} on track {}",
+        note.note_number,
+        note.start,
+        note.track + 1
+    )
+}
+
+fn parse(bytes: &[u8]) -> Result<Vec<Note>, Error> {
+    if bytes.len() < 14 || &bytes[0..4] != b"MThd" {
+        return Err(Error::NotAMidiFile);
+    }
+
+    let header_length = read_u32(bytes, 4)? as usize;
+    let number_of_tracks = read_u16(bytes, 10)? as usize;
+    let division = read_u16(bytes, 12)?;
+    if division & 0x8000 != 0 {
+        return Err(Error::UnsupportedTiming);
+    }
+    let ppqn = i64::from(division.max(1));
+
+    let mut notes = vec![];
+    let mut offset = 8 + header_length;
+
+    for track in 0..number_of_tracks {
+        if bytes.get(offset..offset + 4) != Some(b"MTrk") {
+            return Err(Error::Truncated);
+        }
+        let length = read_u32(bytes, offset + 4)? as usize;
+        let data = bytes
+            .get(offset + 8..offset + 8 + length)
+            .ok_or(Error::Truncated)?;
+
+        let scale = |tick: i64| ((tick * i64::from(PPQN) + ppqn / 2) / ppqn) as i32;
+        for note in parse_track(data)? {
+            notes.push(Note {
+                track,
+                channel: note.channel + 1,
+                note_number: note.note_number,
+                velocity: note.velocity,
+                start: scale(note.start),
+                length: match note.end {
+                    Some(end) => scale(end) - scale(note.start),
+                    None => DEFAULT_LENGTH,
+                },
+            });
+        }
+
+        offset += 8 + length;
+    }
+
+    Ok(notes)
+}
+
+// A note as it appears in the file, in the file's ticks. Notes that are never
+// turned off have no end.
+struct FileNote {
+    channel: i32,
+    start: i64,
+    end: Option<i64>,
+    note_number: i32,
+    velocity: i32,
+}
+
+// Pairs each note off with the earliest note on it could end
+fn parse_track(data: &[u8]) -> Result<Vec<FileNote>, Error> {
+    let mut notes = vec![];
+    let mut open: Vec<(u8, u8, i64, u8)> = vec![];
+    let mut tick = 0;
+    let mut status = 0;
+    let mut i = 0;
+
+    while i < data.len() {
+        let (delta, used) = read_variable_length(&data[i..])?;
+        tick += i64::from(delta);
+        i += used;
+
+        let byte = *data.get(i).ok_or(Error::Truncated)?;
+        if byte & 0x80 != 0 {
+            status = byte;
+            i += 1;
+        }
+
+        match status {
+            0xff => {
+                let (length, used) =
+                    read_variable_length(data.get(i + 1..).ok_or(Error::Truncated)?)?;
+                i += 1 + used + length as usize;
+                status = 0;
+            }
+            0xf0 | 0xf7 => {
+                let (length, used) = read_variable_length(data.get(i..).ok_or(Error::Truncated)?)?;
+                i += used + length as usize;
+                status = 0;
+            }
+            0x80..=0xef => {
+                let channel = status & 0x0f;
+                let data_bytes = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+                let event = data.get(i..i + data_bytes).ok_or(Error::Truncated)?;
+                i += data_bytes;
+
+                let is_note_on = status & 0xf0 == 0x90 && event[1] > 0;
+                let is_note_off = status & 0xf0 == 0x80 || (status & 0xf0 == 0x90 && event[1] == 0);
+
+                if is_note_on {
+                    open.push((channel, event[0], tick, event[1]));
+                } else if is_note_off {
+                    if let Some(n) = open.iter().position(|o| o.0 == channel && o.1 == event[0]) {
+                        let (channel, note_number, start, velocity) = open.remove(n);
+                        notes.push(FileNote {
+                            channel: i32::from(channel),
+                            start,
+                            end: Some(tick),
+                            note_number: i32::from(note_number),
+                            velocity: i32::from(velocity),
+                        });
+                    }
+                }
+            }
+            _ => return Err(Error::NotAMidiFile),
+        }
+    }
+
+    for (channel, note_number, start, velocity) in open {
+        notes.push(FileNote {
+            channel: i32::from(channel),
+            start,
+            end: None,
+            note_number: i32::from(note_number),
+            velocity: i32::from(velocity),
+        });
+    }
+
+    Ok(notes)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Error> {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => Err(Error::Truncated),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(Error::Truncated),
+    }
+}
+
+// Returns the value and the number of bytes it took up
+fn read_variable_length(bytes: &[u8]) -> Result<(u32, usize), Error> {
+    let mut value = 0;
+
+    for (i, byte) in bytes.iter().take(4).enumerate() {
+        value = (value << 7) | u32::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+
+    Err(Error::Truncated)
+}
+
+#[test]
+fn test_variable_length() {
+    assert_eq!(vec![0x00], variable_length(0));
+    assert_eq!(vec![0x7f], variable_length(127));
+    assert_eq!(vec![0x81, 0x00], variable_length(128));
+    assert_eq!(vec![0xff, 0x7f], variable_length(16_383));
+}
+
+#[test]
+fn test_export() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .toggle_step_for_selected_sequence(5);
+    let file = export(&context);
+
+    assert_eq!(b"MThd", &file[0..4]);
+    assert_eq!(&[0, 1, 0, 17, 0, 24], &file[8..14]);
+
+    let note_ons = file.windows(3).filter(|w| w == &[0x90, 36, 127]).count();
+    assert_eq!(2, note_ons);
+}
+
+#[test]
+fn test_export_honours_mute() {
+    let context = Context::default().toggle_step_for_selected_sequence(1);
+    let muted = Context {
+        sequences: vec![context.sequences[0].toggle_mute()],
+        ..context.clone()
+    };
+
+    assert!(export(&muted).windows(3).all(|w| w != [0x90, 36, 127]));
+}
+
+#[cfg(test)]
+fn import_options() -> ImportOptions {
+    ImportOptions {
+        division: Division::sixteenth(),
+        time_signature: TimeSignature::default(),
+        by_track: false,
+    }
+}
+
+#[test]
+fn test_import_exported_sequences() {
+    let context = Context::default()
+        .toggle_step_for_selected_sequence(1)
+        .toggle_step_for_selected_sequence(5)
+        .select_sequence(2)
+        .toggle_step_for_selected_sequence(3);
+
+    let import = import(&export(&context), import_options()).unwrap();
+
+    assert_eq!(2, import.sequences.len());
+    assert!(import.dropped.is_empty());
+    assert_eq!(16, import.sequences[0].number_of_steps());
+    assert_eq!(vec![Step(1), Step(5)], {
+        let mut steps: Vec<Step> = import.sequences[0].active_steps().into_iter().collect();
+        steps.sort_by_key(|s| s.0);
+        steps
+    });
+    assert!(import.sequences[1].active_steps().contains(&Step(3)));
+    assert_eq!(1, import.sequences[0].midi_channel());
+    assert_eq!(3, import.sequences[1].midi_channel());
+}
+
+#[test]
+fn test_import_keeps_channels() {
+    let file = header(1)
+        .into_iter()
+        .chain(track(vec![
+            (0, vec![0x99, 36, 100]),
+            (6, vec![0x89, 36, 0]),
+            (6, vec![0x90, 36, 100]),
+            (12, vec![0x80, 36, 0]),
+        ]))
+        .collect::<Vec<u8>>();
+
+    let import = import(&file, import_options()).unwrap();
+
+    assert_eq!(2, import.sequences.len());
+    assert_eq!(1, import.sequences[0].midi_channel());
+    assert_eq!(10, import.sequences[1].midi_channel());
+    assert!(import.sequences[1].active_steps().contains(&Step(1)));
+    assert!(import.sequences[0].active_steps().contains(&Step(2)));
+}
+
+#[test]
+fn test_import_keeps_offsets() {
+    let file = header(1)
+        .into_iter()
+        .chain(track(vec![
+            (8, vec![0x90, 36, 100]),
+            (14, vec![0x80, 36, 0]),
+        ]))
+        .collect::<Vec<u8>>();
+
+    let import = import(&file, import_options()).unwrap();
+    let playback = Context::default().playback();
+
+    assert!(import.sequences[0].events_for_tick(6, &playback).is_empty());
+    assert_eq!(
+        100,
+        import.sequences[0].events_for_tick(8, &playback)[0].velocity
+    );
+}
+
+#[test]
+fn test_import_reports_notes_that_do_not_fit() {
+    let file = header(1)
+        .into_iter()
+        .chain(track(vec![
+            (0, vec![0x90, 36, 100]),
+            (6, vec![0x80, 36, 0]),
+            (6 * 64, vec![0x90, 36, 100]),
+            (6 * 65, vec![0x80, 36, 0]),
+        ]))
+        .collect::<Vec<u8>>();
+
+    let import = import(&file, import_options()).unwrap();
+
+    assert_eq!(64, import.sequences[0].number_of_steps());
+    assert_eq!(1, import.dropped.len());
+}
+
+#[test]
+fn test_import_with_an_empty_grid() {
+    let file = header(1)
+        .into_iter()
+        .chain(track(vec![
+            (6, vec![0x90, 36, 100]),
+            (12, vec![0x80, 36, 0]),
+        ]))
+        .collect::<Vec<u8>>();
+    let options = ImportOptions {
+        division: Division(0),
+        ..import_options()
+    };
+
+    let import = import(&file, options).unwrap();
+    let playback = Context::default().playback();
+
+    // the grid is a single tick
+    assert_eq!(64, import.sequences[0].loop_length_in_ticks());
+    assert_eq!(1, import.sequences[0].events_for_tick(6, &playback).len());
+}
+
+#[test]
+fn test_import_rejects_other_files() {
+    assert_eq!(
+        Some(Error::NotAMidiFile),
+        import(b"not midi", import_options()).err()
+    );
+}
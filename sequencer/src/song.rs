@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_REPEATS: i32 = 64;
+
+// An arrangement of patterns, played in order
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Song {
+    pub entries: Vec<Entry>,
+    pub repeat: Option<Loop>,
+}
+
+// A pattern and the number of times it plays through before the song moves
+// on
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub pattern: usize,
+    pub repeats: i32,
+}
+
+// After the end entry has played the song goes back to the start entry
+// rather than stopping
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Loop {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Song {
+    pub fn append(&self, pattern: usize, repeats: i32) -> Song {
+        let mut entries = self.entries.clone();
+        entries.push(Entry {
+            pattern,
+            repeats: repeats.clamp(1, MAX_REPEATS),
+        });
+
+        Song {
+            entries,
+            ..self.clone()
+        }
+    }
+
+    // Removing an entry inside the loop shortens it, and removing the only
+    // entry in a loop removes the loop
+    pub fn remove(&self, entry: usize) -> Song {
+        if entry >= self.entries.len() {
+            return self.clone();
+        }
+
+        let mut entries = self.entries.clone();
+        entries.remove(entry);
+
+        let repeat = self.repeat.and_then(|l| {
+            let start = match l.start > entry {
+                true => l.start - 1,
+                false => l.start,
+            };
+            let end = match l.end >= entry {
+                true => l.end.checked_sub(1)?,
+                false => l.end,
+            };
+
+            match start <= end && end < entries.len() {
+                true => Some(Loop { start, end }),
+                false => None,
+            }
+        });
+
+        Song { entries, repeat }
+    }
+
+    pub fn set_loop(&self, repeat: Option<Loop>) -> Song {
+        match repeat {
+            Some(l) if l.start > l.end || l.end >= self.entries.len() => self.clone(),
+            _ => Song {
+                repeat,
+                ..self.clone()
+            },
+        }
+    }
+
+    pub fn entry(&self, entry: usize) -> Option<Entry> {
+        self.entries.get(entry).copied()
+    }
+
+    // The entry to play after the given one, or None at the end of the song
+    pub fn next_entry(&self, entry: usize) -> Option<usize> {
+        match self.repeat {
+            Some(l) if l.end == entry && l.end < self.entries.len() => Some(l.start),
+            _ if entry + 1 < self.entries.len() => Some(entry + 1),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_next_entry() {
+    let song = Song::default().append(0, 1).append(1, 2).append(2, 1);
+
+    assert_eq!(Some(1), song.next_entry(0));
+    assert_eq!(Some(2), song.next_entry(1));
+    assert_eq!(None, song.next_entry(2));
+
+    let song = song.set_loop(Some(Loop { start: 1, end: 2 }));
+    assert_eq!(Some(1), song.next_entry(2));
+}
+
+#[test]
+fn test_append_clamps_repeats() {
+    let song = Song::default().append(0, 0).append(1, 1000);
+
+    assert_eq!(1, song.entries[0].repeats);
+    assert_eq!(MAX_REPEATS, song.entries[1].repeats);
+}
+
+#[test]
+fn test_set_loop_outside_song_is_ignored() {
+    let song = Song::default().append(0, 1);
+
+    assert_eq!(None, song.set_loop(Some(Loop { start: 0, end: 1 })).repeat);
+    assert_eq!(None, song.set_loop(Some(Loop { start: 1, end: 0 })).repeat);
+}
+
+#[test]
+fn test_remove() {
+    let song = Song::default()
+        .append(0, 1)
+        .append(1, 1)
+        .append(2, 1)
+        .append(3, 1)
+        .set_loop(Some(Loop { start: 1, end: 3 }));
+
+    let removed = song.remove(0);
+    assert_eq!(3, removed.entries.len());
+    assert_eq!(Some(Loop { start: 0, end: 2 }), removed.repeat);
+
+    let removed = song.remove(2);
+    assert_eq!(Some(Loop { start: 1, end: 2 }), removed.repeat);
+
+    let removed = song.remove(3).remove(2);
+    assert_eq!(Some(Loop { start: 1, end: 1 }), removed.repeat);
+    assert_eq!(None, removed.remove(1).repeat);
+
+    assert_eq!(song, song.remove(4));
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Resolution {
+    Eighth,
+    Sixteenth,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Swing {
+    pub amount: i32,
+    pub resolution: Resolution,
+}
+
+pub const MIN_AMOUNT: i32 = 50;
+pub const MAX_AMOUNT: i32 = 75;
+
+impl Default for Swing {
+    fn default() -> Swing {
+        Swing {
+            amount: MIN_AMOUNT,
+            resolution: Resolution::Sixteenth,
+        }
+    }
+}
+
+impl Swing {
+    pub fn with_amount(&self, amount: i32) -> Swing {
+        Swing {
+            amount: amount.clamp(MIN_AMOUNT, MAX_AMOUNT),
+            ..*self
+        }
+    }
+
+    pub fn increment_amount(&self) -> Swing {
+        self.with_amount(self.amount + 1)
+    }
+
+    pub fn decrement_amount(&self) -> Swing {
+        self.with_amount(self.amount - 1)
+    }
+
+    pub fn set_resolution(&self, resolution: Resolution) -> Swing {
+        Swing {
+            resolution,
+            ..*self
+        }
+    }
+
+    // Like the MPC, the amount is the percentage of a pair of notes at the
+    // swing resolution that the first note of the pair lasts, so only ticks
+    // that fall exactly on the second note of a pair are delayed
+    pub fn delay(&self, tick: i32) -> i32 {
+        let ticks_per_note = self.ticks_per_note();
+        let ticks_per_pair = ticks_per_note * 2;
+
+        match tick.rem_euclid(ticks_per_pair) == ticks_per_note {
+            true => ((self.amount - MIN_AMOUNT) * ticks_per_pair + 50) / 100,
+            false => 0,
+        }
+    }
+
+    pub fn max_delay(&self) -> i32 {
+        ((MAX_AMOUNT - MIN_AMOUNT) * self.ticks_per_note() * 2 + 50) / 100
+    }
+
+    fn ticks_per_note(&self) -> i32 {
+        match self.resolution {
+            Resolution::Eighth => 12,
+            Resolution::Sixteenth => 6,
+        }
+    }
+}
+
+#[test]
+fn test_no_swing() {
+    let swing = Swing::default();
+
+    for tick in 0..96 {
+        assert_eq!(0, swing.delay(tick));
+    }
+}
+
+#[test]
+fn test_sixteenth_swing() {
+    let swing = Swing::default().with_amount(66);
+
+    assert_eq!(0, swing.delay(0));
+    assert_eq!(2, swing.delay(6));
+    assert_eq!(0, swing.delay(7));
+    assert_eq!(0, swing.delay(12));
+    assert_eq!(2, swing.delay(18));
+}
+
+#[test]
+fn test_eighth_swing() {
+    let swing = Swing::default()
+        .with_amount(75)
+        .set_resolution(Resolution::Eighth);
+
+    assert_eq!(0, swing.delay(6));
+    assert_eq!(6, swing.delay(12));
+    assert_eq!(0, swing.delay(18));
+    assert_eq!(6, swing.delay(36));
+}
+
+#[test]
+fn test_amount_limits() {
+    assert_eq!(50, Swing::default().decrement_amount().amount);
+    assert_eq!(
+        75,
+        Swing::default().with_amount(75).increment_amount().amount
+    );
+}
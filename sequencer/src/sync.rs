@@ -0,0 +1,91 @@
+use std::time::Instant;
+
+// How much each new clock interval moves the tempo estimate. Lower values
+// are steadier but slower to follow tempo changes.
+const SMOOTHING: f64 = 0.1;
+
+// Intervals longer than this (a quarter note at 10bpm) mean the clock
+// stopped and started again, so they say nothing about the tempo
+const MAX_INTERVAL: f64 = 0.25;
+
+// Estimates the tempo of an external MIDI clock from the time between its
+// 24 PPQN clock messages
+pub struct ClockFollower {
+    last_clock: Option<Instant>,
+    seconds_per_tick: Option<f64>,
+}
+
+impl ClockFollower {
+    pub fn new() -> ClockFollower {
+        ClockFollower {
+            last_clock: None,
+            seconds_per_tick: None,
+        }
+    }
+
+    pub fn clock(&mut self, now: Instant) {
+        if let Some(last_clock) = self.last_clock {
+            let interval = (now - last_clock).as_secs_f64();
+
+            if interval < MAX_INTERVAL {
+                self.seconds_per_tick = Some(match self.seconds_per_tick {
+                    Some(estimate) => estimate + SMOOTHING * (interval - estimate),
+                    None => interval,
+                });
+            }
+        }
+
+        self.last_clock = Some(now);
+    }
+
+    pub fn bpm(&self) -> Option<f32> {
+        self.seconds_per_tick
+            .filter(|s| *s > 0.)
+            .map(|s| (60. / (s * 24.)) as f32)
+    }
+}
+
+impl Default for ClockFollower {
+    fn default() -> ClockFollower {
+        ClockFollower::new()
+    }
+}
+
+#[cfg(test)]
+use std::time::Duration;
+
+#[test]
+fn test_bpm_from_steady_clock() {
+    let start = Instant::now();
+    let mut follower = ClockFollower::new();
+    assert_eq!(None, follower.bpm());
+
+    for n in 0..48 {
+        follower.clock(start + Duration::from_micros(20_833 * n));
+    }
+
+    assert_eq!(120, follower.bpm().unwrap().round() as i32);
+}
+
+#[test]
+fn test_bpm_is_smoothed() {
+    let start = Instant::now();
+    let mut follower = ClockFollower::new();
+    follower.clock(start);
+    follower.clock(start + Duration::from_micros(20_833));
+    follower.clock(start + Duration::from_micros(20_833 + 10_000));
+
+    let bpm = follower.bpm().unwrap();
+    assert!(bpm > 120. && bpm < 135.);
+}
+
+#[test]
+fn test_long_gaps_are_ignored() {
+    let start = Instant::now();
+    let mut follower = ClockFollower::new();
+    follower.clock(start);
+    follower.clock(start + Duration::from_micros(20_833));
+    follower.clock(start + Duration::from_secs(5));
+
+    assert_eq!(120, follower.bpm().unwrap().round() as i32);
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+pub const MIN_BPM: f32 = 30.0;
+pub const MAX_BPM: f32 = 240.0;
+
+// How far the tempo is pushed up or down while nudging, as a fraction of
+// the tempo
+pub const NUDGE: f32 = 0.04;
+
+const MAX_TAPS: usize = 8;
+
+// A gradual change of tempo between two ticks
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TempoRamp {
+    pub from: f32,
+    pub to: f32,
+    pub start_tick: i32,
+    pub length: i32,
+}
+
+// Taps further apart than this start a new tempo
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Intervals that differ from the median by more than this fraction are
+// treated as mistimed taps and ignored
+const OUTLIER: f64 = 0.25;
+
+// Clamps to the supported range and rounds to a tenth of a beat per minute
+pub fn clamp_bpm(bpm: f32) -> f32 {
+    ((bpm * 10.).round() / 10.).clamp(MIN_BPM, MAX_BPM)
+}
+
+// Adds a tap to the recent taps, starting again if the last tap was too long
+// ago to be part of the same tempo
+pub fn add_tap(taps: &[Instant], time: Instant) -> Vec<Instant> {
+    let mut taps = match taps.last() {
+        Some(last) if time.duration_since(*last) <= TAP_TIMEOUT => taps.to_vec(),
+        _ => vec![],
+    };
+
+    taps.push(time);
+
+    let excess = taps.len().saturating_sub(MAX_TAPS);
+    taps.split_off(excess)
+}
+
+pub fn tap_tempo(taps: &[Instant]) -> Option<f32> {
+    let mut intervals: Vec<f64> = taps
+        .windows(2)
+        .map(|pair| pair[1].duration_since(pair[0]).as_secs_f64())
+        .collect();
+
+    if intervals.is_empty() {
+        return None;
+    }
+
+    let mut sorted = intervals.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = sorted[sorted.len() / 2];
+
+    intervals.retain(|i| (i - median).abs() <= median * OUTLIER);
+    let average = intervals.iter().sum::<f64>() / intervals.len() as f64;
+
+    match average > 0. {
+        true => Some(clamp_bpm((60. / average) as f32)),
+        false => None,
+    }
+}
+
+impl TempoRamp {
+    pub fn bpm_at(&self, tick: i32) -> f32 {
+        let progress = match self.length {
+            0 => 1.0,
+            _ => ((tick - self.start_tick) as f32 / self.length as f32).clamp(0.0, 1.0),
+        };
+
+        self.from + (self.to - self.from) * progress
+    }
+
+    pub fn is_finished(&self, tick: i32) -> bool {
+        tick >= self.start_tick + self.length
+    }
+}
+
+#[cfg(test)]
+fn taps_at(start: Instant, milliseconds: &[u64]) -> Vec<Instant> {
+    milliseconds.iter().fold(vec![], |taps, ms| {
+        add_tap(&taps, start + Duration::from_millis(*ms))
+    })
+}
+
+#[test]
+fn test_clamp_bpm() {
+    assert_eq!(120.1, clamp_bpm(120.123));
+    assert_eq!(MIN_BPM, clamp_bpm(10.));
+    assert_eq!(MAX_BPM, clamp_bpm(300.));
+}
+
+#[test]
+fn test_tap_tempo() {
+    let start = Instant::now();
+
+    assert_eq!(None, tap_tempo(&taps_at(start, &[0])));
+    assert_eq!(
+        Some(120.0),
+        tap_tempo(&taps_at(start, &[0, 500, 1000, 1500]))
+    );
+}
+
+#[test]
+fn test_tap_tempo_ignores_outliers() {
+    let start = Instant::now();
+    let taps = taps_at(start, &[0, 500, 1000, 1800, 2300, 2800]);
+
+    assert_eq!(Some(120.0), tap_tempo(&taps));
+}
+
+#[test]
+fn test_taps_time_out_and_are_limited() {
+    let start = Instant::now();
+
+    assert_eq!(1, taps_at(start, &[0, 500, 5000]).len());
+    assert_eq!(
+        MAX_TAPS,
+        taps_at(start, &[0, 100, 200, 300, 400, 500, 600, 700, 800, 900]).len()
+    );
+}
+
+#[test]
+fn test_tempo_ramp() {
+    let ramp = TempoRamp {
+        from: 120.0,
+        to: 140.0,
+        start_tick: 96,
+        length: 192,
+    };
+
+    assert_eq!(120.0, ramp.bpm_at(0));
+    assert_eq!(130.0, ramp.bpm_at(192));
+    assert_eq!(140.0, ramp.bpm_at(288));
+    assert!(!ramp.is_finished(287));
+    assert!(ramp.is_finished(288));
+}
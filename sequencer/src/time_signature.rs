@@ -0,0 +1,113 @@
+use crate::division::Division;
+use crate::sequence::MAX_STEPS;
+use serde::{Deserialize, Serialize};
+
+const TICKS_PER_WHOLE_NOTE: i32 = 96;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub beats: i32,
+    pub unit: i32,
+}
+
+// A position in bars and beats, counting from 1 like a DAW, and ticks
+// within the beat
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Position {
+    pub bar: i32,
+    pub beat: i32,
+    pub tick: i32,
+}
+
+impl Default for TimeSignature {
+    fn default() -> TimeSignature {
+        TimeSignature { beats: 4, unit: 4 }
+    }
+}
+
+impl TimeSignature {
+    // Only units that divide a whole note into a whole number of ticks are
+    // supported
+    pub fn new(beats: i32, unit: i32) -> Option<TimeSignature> {
+        match (beats, unit) {
+            (1..=16, 1) | (1..=16, 2) | (1..=16, 4) | (1..=16, 8) | (1..=16, 16) => {
+                Some(TimeSignature { beats, unit })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn ticks_per_beat(&self) -> i32 {
+        TICKS_PER_WHOLE_NOTE / self.unit
+    }
+
+    pub fn ticks_per_bar(&self) -> i32 {
+        self.beats * self.ticks_per_beat()
+    }
+
+    // The number of whole steps of a division that fit in a bar
+    pub fn steps_per_bar(&self, division: Division) -> i32 {
+        (self.ticks_per_bar() / division.ticks_per_step()).clamp(1, MAX_STEPS)
+    }
+
+    pub fn position(&self, tick: i32) -> Position {
+        let tick_in_bar = tick.rem_euclid(self.ticks_per_bar());
+
+        Position {
+            bar: tick.div_euclid(self.ticks_per_bar()) + 1,
+            beat: tick_in_bar / self.ticks_per_beat() + 1,
+            tick: tick_in_bar % self.ticks_per_beat(),
+        }
+    }
+}
+
+#[test]
+fn test_ticks_per_bar() {
+    assert_eq!(96, TimeSignature::default().ticks_per_bar());
+    assert_eq!(72, TimeSignature::new(3, 4).unwrap().ticks_per_bar());
+    assert_eq!(84, TimeSignature::new(7, 8).unwrap().ticks_per_bar());
+    assert_eq!(72, TimeSignature::new(6, 8).unwrap().ticks_per_bar());
+    assert_eq!(None, TimeSignature::new(4, 3));
+}
+
+#[test]
+fn test_steps_per_bar() {
+    let seven_eight = TimeSignature::new(7, 8).unwrap();
+
+    assert_eq!(
+        16,
+        TimeSignature::default().steps_per_bar(Division::sixteenth())
+    );
+    assert_eq!(14, seven_eight.steps_per_bar(Division::sixteenth()));
+    assert_eq!(7, seven_eight.steps_per_bar(Division::eighth()));
+}
+
+#[test]
+fn test_position() {
+    let three_four = TimeSignature::new(3, 4).unwrap();
+
+    assert_eq!(
+        Position {
+            bar: 1,
+            beat: 1,
+            tick: 0
+        },
+        three_four.position(0)
+    );
+    assert_eq!(
+        Position {
+            bar: 2,
+            beat: 2,
+            tick: 5
+        },
+        three_four.position(72 + 24 + 5)
+    );
+    assert_eq!(
+        Position {
+            bar: 0,
+            beat: 3,
+            tick: 0
+        },
+        three_four.position(-24)
+    );
+}
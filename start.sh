@@ -9,5 +9,5 @@ o2m > /dev/null &
 
 sleep 3
 
-./sequencer/target/release/sequencer --controller $CONTROLLER > /dev/null &
+./sequencer/target/release/sequencer --controller $CONTROLLER --project ./project.json > /dev/null &
 sclang ./sampler/sampler.scd > /dev/null &